@@ -0,0 +1,80 @@
+use serde::{Serialize, Deserialize};
+
+/// Exposure settings that scale radiance before it is written to the image.
+/// Radiance is written unscaled at the neutral exposure of f/8, 1/125 s and ISO 100
+/// with no compensation. Settings that are not given take those neutral values.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Exposure {
+    /// Exposure compensation in stops.
+    #[serde(default)]
+    pub ev_compensation: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iso: Option<f64>,
+    /// Shutter speed in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shutter_speed: Option<f64>,
+    /// When given, the f-number also sets the lens aperture. See [`super::Camera::lens_radius`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub f_number: Option<f64>,
+    /// Height of the sensor in scene units, used to derive the physical focal length
+    /// from the field of view. The default is a full-frame sensor in a scene measured in metres.
+    #[serde(default = "default_sensor_height")]
+    pub sensor_height: f64,
+}
+
+const NEUTRAL_F_NUMBER: f64 = 8.0;
+const NEUTRAL_SHUTTER_SPEED: f64 = 1.0 / 125.0;
+const NEUTRAL_ISO: f64 = 100.0;
+
+/// Exposure value at ISO 100 of the given settings.
+fn ev100(f_number: f64, shutter_speed: f64, iso: f64) -> f64 {
+    (f_number * f_number / shutter_speed * 100.0 / iso).log2()
+}
+
+fn default_sensor_height() -> f64 {
+    0.024
+}
+
+impl Default for Exposure {
+    fn default() -> Self {
+        Self {
+            ev_compensation: 0.0,
+            iso: None,
+            shutter_speed: None,
+            f_number: None,
+            sensor_height: default_sensor_height(),
+        }
+    }
+}
+
+impl Exposure {
+    /// Factor applied to the accumulated radiance of every pixel.
+    /// A sensor saturating at 1 / (1.2 · 2^EV100) is normalised so that the neutral exposure gives 1.
+    pub fn scale(&self) -> f64 {
+        let ev = ev100(
+            self.f_number.unwrap_or(NEUTRAL_F_NUMBER),
+            self.shutter_speed.unwrap_or(NEUTRAL_SHUTTER_SPEED),
+            self.iso.unwrap_or(NEUTRAL_ISO),
+        );
+        let neutral = ev100(NEUTRAL_F_NUMBER, NEUTRAL_SHUTTER_SPEED, NEUTRAL_ISO);
+        (neutral - ev + self.ev_compensation).exp2()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neutral_exposure_is_unscaled() {
+        let exposure = Exposure { f_number: Some(8.0), shutter_speed: Some(1.0 / 125.0), ..Default::default() };
+        assert!((exposure.scale() - 1.0).abs() < 1e-12);
+        assert!((Exposure::default().scale() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn one_stop_per_doubling() {
+        let exposure = Exposure { iso: Some(200.0), shutter_speed: Some(1.0 / 250.0), ev_compensation: 1.0, ..Default::default() };
+        assert!((exposure.scale() - 2.0).abs() < 1e-12);
+    }
+}
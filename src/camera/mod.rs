@@ -1,44 +1,82 @@
+mod exposure;
+mod projection;
+mod region;
 mod screen;
+mod view;
 
-use glm::DVec3;
 use nalgebra_glm as glm;
+pub use exposure::Exposure;
+pub use projection::Projection;
+pub use region::Region;
 pub use screen::Screen;
+pub use view::View;
+use rand::Rng;
 use serde::{Serialize, Deserialize};
 
+use crate::hit::Ray;
+use crate::utils::{random_in_unit_disk, Interpolate};
+
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct Camera {
     pub screen: screen::Screen,
-    pub viewport_height: f64,
-    pub origin: glm::DVec3,
-    pub focal_len: f64,
-    pub pitch: f64,
-    pub yaw: f64,
+    #[serde(flatten)]
+    pub view: View,
+    #[serde(default)]
+    pub projection: Projection,
+    /// Radius of the thin lens. Zero gives a pinhole camera.
+    /// Ignored if the exposure settings give an f-number.
+    #[serde(default)]
+    pub aperture_radius: f64,
+    /// Distance from the lens to the plane in perfect focus, measured along the view direction.
+    /// Defaults to the distance of the image plane (the look-at target or `focal_len`)
+    /// with `aperture_radius`, and must be given with an f-number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus_distance: Option<f64>,
+    /// Time at which the shutter opens. Ray times are sampled uniformly between opening and closing.
+    #[serde(default)]
+    pub shutter_open: f64,
+    /// Time at which the shutter closes. Shapes with motion move from their start pose at time 0
+    /// to their end pose at time 1.
+    #[serde(default = "default_shutter_close")]
+    pub shutter_close: f64,
+    #[serde(default)]
+    pub exposure: Exposure,
+}
+
+fn default_shutter_close() -> f64 {
+    1.0
 }
 
 impl Default for Camera {
     fn default() -> Self {
         Self {
             screen: Screen::default(),
-            viewport_height: 2.0,
-            focal_len: 1.0,
-            origin: glm::DVec3::zeros(),
-            pitch: 0.0,
-            yaw: 0.0,
+            view: View::default(),
+            projection: Projection::default(),
+            aperture_radius: 0.0,
+            focus_distance: None,
+            shutter_open: 0.0,
+            shutter_close: default_shutter_close(),
+            exposure: Exposure::default(),
         }
     }
 }
 
 impl Camera {
     fn rotation_matrix(&self) -> glm::DMat4 {
-        glm::rotation(-self.pitch, &DVec3::new(0.0, 1.0, 0.0)) * glm::rotation(self.yaw, &DVec3::new(1.0, 0.0, 0.0))
+        self.view.rotation_matrix()
+    }
+
+    pub fn origin(&self) -> glm::DVec3 {
+        self.view.origin()
     }
 
     pub fn viewport_width(&self) -> f64 {
-        self.viewport_height * self.screen.aspect_ratio()
+        self.viewport_height() * self.screen.aspect_ratio()
     }
 
     pub fn viewport_height(&self) -> f64 {
-        self.viewport_height
+        self.view.viewport_height()
     }
 
     pub fn horizontal_vec(&self) -> glm::DVec3 {
@@ -51,10 +89,144 @@ impl Camera {
 
     /// Orientation vector of the camera. Its length is equal to the focal length.
     pub fn orient_vec(&self) -> glm::DVec3 {
-        (self.rotation_matrix() * glm::DVec4::new(0.0, 0.0, -self.focal_len, 1.0)).xyz()
+        (self.rotation_matrix() * glm::DVec4::new(0.0, 0.0, -self.view.focal_len(), 1.0)).xyz()
     }
 
     pub fn left_bottom_vec(&self) -> glm::DVec3 {
-        self.origin - 0.5 * self.horizontal_vec() - 0.5 * self.vertical_vec() + self.orient_vec()
+        self.origin() - 0.5 * self.horizontal_vec() - 0.5 * self.vertical_vec() + self.orient_vec()
+    }
+
+    /// Whether perspective rays keep the aim that pitch-yaw scenes have always been rendered with:
+    /// the point on the viewport itself is the direction, which frames the view as if the camera
+    /// sat at the world origin. Look-at views and cameras with any lens setting (`aperture_radius`,
+    /// `focus_distance` or an f-number) aim from the camera, so that the focal plane lies
+    /// in front of it. A pitch-yaw scene with its camera away from the world origin is framed
+    /// differently once it adds a lens, so check its framing when adding one.
+    pub fn legacy_aim(&self) -> bool {
+        matches!(self.view, View::PitchYaw { .. })
+            && self.aperture_radius <= 0.0
+            && self.focus_distance.is_none()
+            && self.exposure.f_number.is_none()
+    }
+
+    /// Radius of the lens aperture. With an f-number N, this is f / 2N where f is the focal length
+    /// of a lens with the camera's field of view on the configured sensor.
+    pub fn lens_radius(&self) -> f64 {
+        match self.exposure.f_number {
+            Some(f_number) => {
+                let focal_length = self.exposure.sensor_height * self.view.focal_len() / self.viewport_height();
+                0.5 * focal_length / f_number
+            }
+            None => self.aperture_radius,
+        }
+    }
+
+    /// Check settings that serde cannot check field by field.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(self.exposure.f_number.is_none() || self.focus_distance.is_some(),
+            "the f-number opens the lens aperture, so the camera also needs a focus_distance");
+        Ok(())
+    }
+
+    /// Left and right eye cameras of a parallel stereo rig,
+    /// each moved sideways by half of `interocular_distance`.
+    pub fn stereo_pair(&self, interocular_distance: f64) -> (Camera, Camera) {
+        let offset = 0.5 * interocular_distance * self.horizontal_vec().normalize();
+        (
+            Camera { view: self.view.translated(&-offset), ..*self },
+            Camera { view: self.view.translated(&offset), ..*self },
+        )
+    }
+
+    fn sample_time(&self) -> f64 {
+        if self.shutter_close > self.shutter_open {
+            rand::thread_rng().gen_range(self.shutter_open..self.shutter_close)
+        } else {
+            self.shutter_open
+        }
+    }
+
+    /// Generate a primary ray through screen coordinate (u, v), both in [0, 1].
+    /// Returns None if the projection does not cover (u, v).
+    /// When the aperture is non-zero, the ray starts from a random point on the lens disk
+    /// and passes through the point in focus that the pinhole ray would hit.
+    pub fn get_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let (origin, direction) = self.projection.primary_ray(self, u, v)?;
+        let time = self.sample_time();
+        let lens_radius = self.lens_radius();
+        if lens_radius <= 0.0 {
+            return Some(Ray::new(origin, direction).with_time(time));
+        }
+
+        let direction = direction.normalize();
+        let focus_distance = self.focus_distance.unwrap_or_else(|| self.view.focal_len());
+        let focus_t = if self.projection.has_focal_plane() {
+            focus_distance / direction.dot(&self.orient_vec().normalize())
+        } else {
+            focus_distance
+        };
+        let focus_point = origin + focus_t * direction;
+        let lens = lens_radius * random_in_unit_disk();
+        let lens_origin = origin
+            + lens.x * self.horizontal_vec().normalize()
+            + lens.y * self.vertical_vec().normalize();
+        Some(Ray::new(lens_origin, focus_point - lens_origin).with_time(time))
+    }
+}
+
+impl Interpolate for Camera {
+    /// Screen, projection and exposure are taken from `self`; everything else is blended.
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        Self {
+            screen: self.screen,
+            view: self.view.interpolate(&other.view, t),
+            projection: self.projection,
+            aperture_radius: self.aperture_radius.interpolate(&other.aperture_radius, t),
+            focus_distance: match (self.focus_distance, other.focus_distance) {
+                (Some(a), Some(b)) => Some(a.interpolate(&b, t)),
+                (a, b) => a.or(b),
+            },
+            shutter_open: self.shutter_open.interpolate(&other.shutter_open, t),
+            shutter_close: self.shutter_close.interpolate(&other.shutter_close, t),
+            exposure: self.exposure,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pitch_yaw_camera() -> Camera {
+        let view = View::PitchYaw { viewport_height: 2.0, origin: glm::DVec3::new(0.0, 0.0, 0.4), focal_len: 2.0, pitch: 0.3, yaw: 0.1, roll: 0.0 };
+        Camera { view, ..Camera::default() }
+    }
+
+    #[test]
+    fn pitch_yaw_cameras_without_a_lens_keep_their_framing() {
+        let camera = pitch_yaw_camera();
+        let ray = camera.get_ray(0.25, 0.75).unwrap();
+        let legacy = camera.left_bottom_vec() + 0.25 * camera.horizontal_vec() + 0.75 * camera.vertical_vec();
+        assert_eq!(ray.origin, camera.origin());
+        assert!((ray.direction - legacy.normalize()).norm() < 1e-12);
+    }
+
+    #[test]
+    fn cameras_with_a_lens_aim_from_the_camera() {
+        let camera = Camera { aperture_radius: 0.1, focus_distance: Some(3.0), ..pitch_yaw_camera() };
+        let (origin, direction) = camera.projection.primary_ray(&camera, 0.5, 0.5).unwrap();
+        assert_eq!(origin, camera.origin());
+        assert!((direction.normalize() - camera.orient_vec().normalize()).norm() < 1e-12);
+    }
+
+    #[test]
+    fn lens_focuses_on_the_look_at_target_by_default() {
+        let target = glm::DVec3::new(1.0, 2.0, -5.0);
+        let view = View::LookAt { eye: glm::DVec3::new(1.0, 2.0, 0.0), target, up: glm::DVec3::y(), vfov: 40.0 };
+        let camera = Camera { view, aperture_radius: 0.5, ..Camera::default() };
+        for _ in 0..16 {
+            let ray = camera.get_ray(0.5, 0.5).unwrap();
+            assert!((target - ray.origin).normalize().cross(&ray.direction).norm() < 1e-9);
+        }
     }
 }
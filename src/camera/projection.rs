@@ -0,0 +1,71 @@
+use std::f64::consts::PI;
+
+use glm::DVec3;
+use nalgebra_glm as glm;
+use serde::{Serialize, Deserialize};
+
+use super::Camera;
+
+/// How screen coordinates are mapped to primary rays.
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Projection {
+    /// Pinhole projection through the viewport.
+    #[default]
+    Perspective,
+    /// Parallel rays along the view direction. The viewport size is the size of the view volume.
+    Orthographic,
+    /// Equidistant fisheye. `fov` is the angle in degrees covered by the inscribed image circle;
+    /// pixels outside of the circle are black.
+    Fisheye {
+        #[serde(default = "default_fisheye_fov")]
+        fov: f64,
+    },
+    /// 360° latitude-longitude panorama centered on the view direction.
+    Equirectangular,
+}
+
+fn default_fisheye_fov() -> f64 {
+    180.0
+}
+
+impl Projection {
+    /// Returns the origin and direction of the pinhole ray through screen coordinate (u, v),
+    /// or None if (u, v) is not covered by the projection.
+    pub fn primary_ray(&self, camera: &Camera, u: f64, v: f64) -> Option<(DVec3, DVec3)> {
+        let origin = camera.origin();
+        let point_on_viewport = camera.left_bottom_vec() + u * camera.horizontal_vec() + v * camera.vertical_vec();
+        let right = camera.horizontal_vec().normalize();
+        let up = camera.vertical_vec().normalize();
+        let forward = camera.orient_vec().normalize();
+
+        match *self {
+            Projection::Perspective if camera.legacy_aim() => Some((origin, point_on_viewport)),
+            Projection::Perspective => Some((origin, point_on_viewport - origin)),
+            Projection::Orthographic => Some((point_on_viewport - camera.orient_vec(), camera.orient_vec())),
+            Projection::Fisheye { fov } => {
+                let x = (2.0 * u - 1.0) * camera.screen.aspect_ratio();
+                let y = 2.0 * v - 1.0;
+                let r = x.hypot(y);
+                if r > 1.0 {
+                    return None;
+                }
+                let theta = r * 0.5 * fov.to_radians();
+                let side = if r > 0.0 { (x / r) * right + (y / r) * up } else { DVec3::zeros() };
+                Some((origin, theta.cos() * forward + theta.sin() * side))
+            }
+            Projection::Equirectangular => {
+                let phi = (u - 0.5) * 2.0 * PI;
+                let theta = (v - 0.5) * PI;
+                let direction = theta.cos() * phi.sin() * right + theta.sin() * up + theta.cos() * phi.cos() * forward;
+                Some((origin, direction))
+            }
+        }
+    }
+
+    /// Whether points in focus lie on a plane perpendicular to the view direction.
+    /// Otherwise they lie on a sphere around the lens.
+    pub fn has_focal_plane(&self) -> bool {
+        matches!(self, Projection::Perspective | Projection::Orthographic)
+    }
+}
@@ -0,0 +1,93 @@
+use std::{ops::Range, str::FromStr};
+
+use serde::{Serialize, Deserialize};
+
+use super::Screen;
+
+/// Window of the output image to trace, in pixels from the top left corner.
+/// `x1` and `y1` are exclusive.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Region {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+    /// Write only the region instead of a full-size image with the rest black.
+    #[serde(default)]
+    pub crop: bool,
+}
+
+impl Region {
+    pub fn width(&self) -> u32 {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> u32 {
+        self.y1 - self.y0
+    }
+
+    /// Screen rows covered by the region. +y on the screen is -y in the picture.
+    pub fn screen_rows(&self, screen: &Screen) -> Range<u32> {
+        (screen.height - self.y1)..(screen.height - self.y0)
+    }
+
+    /// Pixel of the output image showing the screen point `(x, y)`.
+    pub fn image_pixel(&self, screen: &Screen, x: u32, y: u32) -> (u32, u32) {
+        let (offset_x, offset_y) = if self.crop { (self.x0, self.y0) } else { (0, 0) };
+        (x - offset_x, screen.height - 1 - y - offset_y)
+    }
+
+    pub fn validate(&self, screen: &Screen) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.x0 < self.x1 && self.y0 < self.y1 && self.x1 <= screen.width && self.y1 <= screen.height,
+            "region {},{},{},{} is empty or outside of the {}x{} screen",
+            self.x0, self.y0, self.x1, self.y1, screen.width, screen.height
+        );
+        Ok(())
+    }
+}
+
+impl FromStr for Region {
+    type Err = anyhow::Error;
+
+    /// Parse `x0,y0,x1,y1`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s.split(',').map(|v| v.trim().parse::<u32>()).collect::<Result<Vec<_>, _>>()?;
+        match values[..] {
+            [x0, y0, x1, y1] => Ok(Region { x0, y0, x1, y1, crop: false }),
+            _ => anyhow::bail!("expected x0,y0,x1,y1 but got {:?}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bottom_screen_rows_map_to_the_top_of_the_picture() {
+        let screen = Screen::new(4, 10);
+        let region: Region = "1,0,3,2".parse().unwrap();
+        assert_eq!(region.screen_rows(&screen), 8..10);
+        assert_eq!(region.image_pixel(&screen, 1, 9), (1, 0));
+        assert_eq!(region.image_pixel(&screen, 2, 8), (2, 1));
+    }
+
+    #[test]
+    fn cropped_regions_start_at_the_image_corner() {
+        let screen = Screen::new(4, 10);
+        let region = Region { crop: true, .."1,6,3,9".parse().unwrap() };
+        assert_eq!(region.screen_rows(&screen), 1..4);
+        assert_eq!(region.image_pixel(&screen, 1, 3), (0, 0));
+        assert_eq!(region.image_pixel(&screen, 2, 1), (1, 2));
+    }
+
+    #[test]
+    fn regions_outside_of_the_screen_are_rejected() {
+        let screen = Screen::new(4, 10);
+        assert!("0,0,4,10".parse::<Region>().unwrap().validate(&screen).is_ok());
+        assert!("0,0,5,10".parse::<Region>().unwrap().validate(&screen).is_err());
+        assert!("2,3,2,5".parse::<Region>().unwrap().validate(&screen).is_err());
+        assert!("1,2,3".parse::<Region>().is_err());
+    }
+}
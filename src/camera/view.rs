@@ -0,0 +1,170 @@
+use glm::DVec3;
+use nalgebra_glm as glm;
+use serde::{Serialize, Deserialize};
+
+use crate::utils::Interpolate;
+
+/// Position and orientation of a camera.
+/// Either form produces the same basis; see [`super::Camera`] for how it is used.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum View {
+    /// Camera at `eye` looking at `target`. `vfov` is the vertical field of view in degrees.
+    /// The image plane passes through `target`.
+    LookAt {
+        eye: DVec3,
+        target: DVec3,
+        #[serde(default = "default_up")]
+        up: DVec3,
+        vfov: f64,
+    },
+    /// Camera at `origin` looking at -z, rotated by `roll` around z, then `yaw` around x,
+    /// then `pitch` around y. All angles are in radians.
+    PitchYaw {
+        viewport_height: f64,
+        origin: DVec3,
+        focal_len: f64,
+        pitch: f64,
+        yaw: f64,
+        #[serde(default)]
+        roll: f64,
+    },
+}
+
+fn default_up() -> DVec3 {
+    DVec3::new(0.0, 1.0, 0.0)
+}
+
+/// The world axis least aligned with `back`, used when the given `up` does not fix a basis.
+fn fallback_up(back: &DVec3) -> DVec3 {
+    let axes = [DVec3::new(0.0, 1.0, 0.0), DVec3::new(0.0, 0.0, 1.0), DVec3::new(1.0, 0.0, 0.0)];
+    axes.into_iter()
+        .min_by(|a, b| a.dot(back).abs().total_cmp(&b.dot(back).abs()))
+        .unwrap()
+}
+
+impl Default for View {
+    fn default() -> Self {
+        View::PitchYaw {
+            viewport_height: 2.0,
+            focal_len: 1.0,
+            origin: DVec3::zeros(),
+            pitch: 0.0,
+            yaw: 0.0,
+            roll: 0.0,
+        }
+    }
+}
+
+impl View {
+    pub fn origin(&self) -> DVec3 {
+        match *self {
+            View::LookAt { eye, .. } => eye,
+            View::PitchYaw { origin, .. } => origin,
+        }
+    }
+
+    pub fn focal_len(&self) -> f64 {
+        match *self {
+            View::LookAt { eye, target, .. } => (target - eye).norm(),
+            View::PitchYaw { focal_len, .. } => focal_len,
+        }
+    }
+
+    pub fn viewport_height(&self) -> f64 {
+        match *self {
+            View::LookAt { vfov, .. } => 2.0 * (0.5 * vfov.to_radians()).tan() * self.focal_len(),
+            View::PitchYaw { viewport_height, .. } => viewport_height,
+        }
+    }
+
+    /// Rotation from camera space (looking at -z, +y up) to world space.
+    pub fn rotation_matrix(&self) -> glm::DMat4 {
+        match *self {
+            View::LookAt { eye, target, up, .. } => {
+                let back = (eye - target).normalize();
+                let right = up.cross(&back);
+                let right = if right.norm() > 1e-9 * up.norm() {
+                    right.normalize()
+                } else {
+                    // `up` is parallel to the view direction; any perpendicular vector will do.
+                    fallback_up(&back).cross(&back).normalize()
+                };
+                let up = back.cross(&right);
+                glm::DMat4::from_columns(&[
+                    right.push(0.0),
+                    up.push(0.0),
+                    back.push(0.0),
+                    glm::DVec4::new(0.0, 0.0, 0.0, 1.0),
+                ])
+            }
+            View::PitchYaw { pitch, yaw, roll, .. } => {
+                glm::rotation(-pitch, &DVec3::new(0.0, 1.0, 0.0))
+                    * glm::rotation(yaw, &DVec3::new(1.0, 0.0, 0.0))
+                    * glm::rotation(roll, &DVec3::new(0.0, 0.0, 1.0))
+            }
+        }
+    }
+
+    /// The same view with the camera moved by `offset`, keeping its orientation.
+    pub fn translated(&self, offset: &DVec3) -> View {
+        let mut view = *self;
+        match view {
+            View::LookAt { ref mut eye, ref mut target, .. } => {
+                *eye += offset;
+                *target += offset;
+            }
+            View::PitchYaw { ref mut origin, .. } => *origin += offset,
+        }
+        view
+    }
+
+    /// The same view in look-at form.
+    pub fn as_look_at(&self) -> View {
+        let rotation = self.rotation_matrix();
+        let eye = self.origin();
+        View::LookAt {
+            eye,
+            target: eye + (rotation * glm::DVec4::new(0.0, 0.0, -self.focal_len(), 0.0)).xyz(),
+            up: (rotation * glm::DVec4::new(0.0, 1.0, 0.0, 0.0)).xyz(),
+            vfov: 2.0 * (0.5 * self.viewport_height() / self.focal_len()).atan().to_degrees(),
+        }
+    }
+}
+
+impl Interpolate for View {
+    /// Views are blended in look-at form.
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        match (self.as_look_at(), other.as_look_at()) {
+            (
+                View::LookAt { eye: e1, target: t1, up: u1, vfov: f1 },
+                View::LookAt { eye: e2, target: t2, up: u2, vfov: f2 },
+            ) => View::LookAt {
+                eye: e1.interpolate(&e2, t),
+                target: t1.interpolate(&t2, t),
+                up: u1.interpolate(&u2, t),
+                vfov: f1.interpolate(&f2, t),
+            },
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn look_at_with_up_parallel_to_view_has_finite_basis() {
+        let view = View::LookAt {
+            eye: DVec3::new(0.0, 5.0, 0.0),
+            target: DVec3::zeros(),
+            up: DVec3::new(0.0, 1.0, 0.0),
+            vfov: 40.0,
+        };
+        let rotation = view.rotation_matrix();
+        assert!(rotation.iter().all(|x| x.is_finite()));
+        let forward = (rotation * glm::DVec4::new(0.0, 0.0, -1.0, 0.0)).xyz();
+        assert!((forward - DVec3::new(0.0, -1.0, 0.0)).norm() < 1e-12);
+    }
+}
@@ -0,0 +1,217 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, bail, ensure, Context};
+
+use super::document::{Accessor, Document};
+
+const GLB_MAGIC: &[u8] = b"glTF";
+const CHUNK_JSON: u32 = 0x4E4F534A;
+const CHUNK_BIN: u32 = 0x004E4942;
+
+fn read_u32(bytes: &[u8], offset: usize) -> anyhow::Result<u32> {
+    let word = bytes.get(offset..offset + 4).ok_or_else(|| anyhow!("truncated GLB file"))?;
+    Ok(u32::from_le_bytes(word.try_into().unwrap()))
+}
+
+/// Split a binary glTF file into its JSON chunk and optional binary chunk.
+pub fn split_glb(bytes: &[u8]) -> anyhow::Result<(&[u8], Option<&[u8]>)> {
+    ensure!(bytes.starts_with(GLB_MAGIC), "not a GLB file");
+    ensure!(read_u32(bytes, 4)? == 2, "only glTF 2.0 is supported");
+    let (mut json, mut bin) = (None, None);
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let length = read_u32(bytes, offset)? as usize;
+        let kind = read_u32(bytes, offset + 4)?;
+        let data = bytes.get(offset + 8..offset + 8 + length).ok_or_else(|| anyhow!("truncated GLB chunk"))?;
+        match kind {
+            CHUNK_JSON => json = Some(data),
+            CHUNK_BIN => bin = Some(data),
+            // chunks of unknown types are skipped, as the spec requires
+            _ => {}
+        }
+        offset += 8 + length;
+    }
+    Ok((json.ok_or_else(|| anyhow!("GLB file has no JSON chunk"))?, bin))
+}
+
+fn decode_base64(text: &str) -> anyhow::Result<Vec<u8>> {
+    let value = |c: u8| -> anyhow::Result<u32> {
+        Ok(match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => bail!("invalid base64 character {:?}", c as char),
+        } as u32)
+    };
+    let digits = text.bytes().filter(|c| !c.is_ascii_whitespace() && *c != b'=').collect::<Vec<_>>();
+    let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+    for chunk in digits.chunks(4) {
+        let mut word = 0;
+        for (i, &c) in chunk.iter().enumerate() {
+            word |= value(c)? << (18 - 6 * i);
+        }
+        bytes.extend_from_slice(&word.to_be_bytes()[1..chunk.len()]);
+    }
+    Ok(bytes)
+}
+
+/// Contents of a buffer or image given by URI: either an embedded base64 data URI
+/// or a file relative to the glTF file.
+pub fn load_uri(uri: &str, base_dir: &Path) -> anyhow::Result<Vec<u8>> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, encoded) = data.split_once(";base64,").ok_or_else(|| anyhow!("only base64 data URIs are supported"))?;
+        return decode_base64(encoded);
+    }
+    let path = base_dir.join(percent_decode(uri)?);
+    fs::read(&path).with_context(|| format!("failed to read {}", path.display()))
+}
+
+/// Replace the `%XX` escapes of a relative URI with the bytes they stand for.
+pub fn percent_decode(uri: &str) -> anyhow::Result<String> {
+    let mut bytes = Vec::with_capacity(uri.len());
+    let mut rest = uri.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = tail.get(..2).and_then(|h| std::str::from_utf8(h).ok())
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+                .ok_or_else(|| anyhow!("invalid percent escape in URI {}", uri))?;
+            bytes.push(hex);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).with_context(|| format!("URI {} is not UTF-8 after decoding", uri))
+}
+
+/// The buffers of a document, from which accessors and buffer views are read.
+pub struct Buffers<'a> {
+    pub document: &'a Document,
+    pub data: Vec<Vec<u8>>,
+}
+
+impl Buffers<'_> {
+    pub fn view(&self, index: usize) -> anyhow::Result<&[u8]> {
+        let view = self.document.buffer_views.get(index).ok_or_else(|| anyhow!("missing buffer view {}", index))?;
+        let buffer = self.data.get(view.buffer).ok_or_else(|| anyhow!("missing buffer {}", view.buffer))?;
+        buffer.get(view.byte_offset..view.byte_offset + view.byte_length)
+            .ok_or_else(|| anyhow!("buffer view {} is out of its buffer", index))
+    }
+
+    /// Elements of the accessor, each with as many components as its type has.
+    /// Normalized integers are mapped to [0, 1] or [-1, 1].
+    pub fn read(&self, index: usize) -> anyhow::Result<Vec<Vec<f64>>> {
+        let accessor: &Accessor = self.document.accessors.get(index).ok_or_else(|| anyhow!("missing accessor {}", index))?;
+        let components = match accessor.kind.as_str() {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            "VEC4" => 4,
+            "MAT2" => 4,
+            "MAT3" => 9,
+            "MAT4" => 16,
+            other => bail!("unknown accessor type {}", other),
+        };
+        let (size, max): (usize, f64) = match accessor.component_type {
+            5120 => (1, i8::MAX as f64),
+            5121 => (1, u8::MAX as f64),
+            5122 => (2, i16::MAX as f64),
+            5123 => (2, u16::MAX as f64),
+            5125 => (4, u32::MAX as f64),
+            5126 => (4, 1.0),
+            other => bail!("unknown accessor component type {}", other),
+        };
+        if accessor.sparse.is_some() {
+            eprintln!("warning: sparse accessor {} is read without its sparse values", index);
+        }
+        // accessors without a buffer view are all zeros
+        let Some(view_index) = accessor.buffer_view else {
+            return Ok(vec![vec![0.0; components]; accessor.count]);
+        };
+        let view = self.view(view_index)?;
+        let stride = self.document.buffer_views[view_index].byte_stride.unwrap_or(components * size);
+
+        let component = |offset: usize| -> anyhow::Result<f64> {
+            let b = view.get(offset..offset + size).ok_or_else(|| anyhow!("accessor {} is out of its buffer view", index))?;
+            let value = match accessor.component_type {
+                5120 => b[0] as i8 as f64,
+                5121 => b[0] as f64,
+                5122 => i16::from_le_bytes([b[0], b[1]]) as f64,
+                5123 => u16::from_le_bytes([b[0], b[1]]) as f64,
+                5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            };
+            Ok(if accessor.normalized { (value / max).max(-1.0) } else { value })
+        };
+        (0..accessor.count)
+            .map(|i| (0..components).map(|c| component(accessor.byte_offset + i * stride + c * size)).collect())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(kind: u32, data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_le_bytes().to_vec();
+        chunk.extend(kind.to_le_bytes());
+        chunk.extend(data);
+        chunk
+    }
+
+    fn glb(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body = chunks.concat();
+        let mut glb = GLB_MAGIC.to_vec();
+        glb.extend(2u32.to_le_bytes());
+        glb.extend((12 + body.len() as u32).to_le_bytes());
+        glb.extend(body);
+        glb
+    }
+
+    #[test]
+    fn glb_is_split_into_json_and_binary_chunks() {
+        let bytes = glb(&[chunk(CHUNK_JSON, b"{}  "), chunk(0x12345678, b"skip"), chunk(CHUNK_BIN, &[1, 2, 3, 4])]);
+        let (json, bin) = split_glb(&bytes).unwrap();
+        assert_eq!(json, b"{}  ");
+        assert_eq!(bin, Some(&[1u8, 2, 3, 4][..]));
+    }
+
+    #[test]
+    fn glb_without_json_or_with_truncated_chunk_is_rejected() {
+        assert!(split_glb(&glb(&[chunk(CHUNK_BIN, &[0; 4])])).is_err());
+        let mut truncated = glb(&[chunk(CHUNK_JSON, b"{}  ")]);
+        truncated.pop();
+        assert!(split_glb(&truncated).is_err());
+        assert!(split_glb(b"not a glb file").is_err());
+    }
+
+    #[test]
+    fn base64_decodes_with_and_without_padding() {
+        assert_eq!(decode_base64("TWFu").unwrap(), b"Man");
+        assert_eq!(decode_base64("TWE=").unwrap(), b"Ma");
+        assert_eq!(decode_base64("TQ==").unwrap(), b"M");
+        assert_eq!(decode_base64("TQ").unwrap(), b"M");
+        assert_eq!(decode_base64("-_8\n").unwrap(), [0xfb, 0xff]);
+        assert!(decode_base64("T!==").is_err());
+    }
+
+    #[test]
+    fn data_uris_are_decoded_without_touching_the_disk() {
+        let bytes = load_uri("data:application/octet-stream;base64,AAEC", Path::new("/nonexistent")).unwrap();
+        assert_eq!(bytes, [0, 1, 2]);
+    }
+
+    #[test]
+    fn percent_escapes_are_decoded() {
+        assert_eq!(percent_decode("my%20model%2Ebin").unwrap(), "my model.bin");
+        assert_eq!(percent_decode("caf%C3%A9.png").unwrap(), "café.png");
+        assert_eq!(percent_decode("plain.bin").unwrap(), "plain.bin");
+        assert!(percent_decode("bad%2").is_err());
+        assert!(percent_decode("bad%zz").is_err());
+        assert!(percent_decode("%FF").is_err());
+    }
+}
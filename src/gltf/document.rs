@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+/// The parts of the glTF 2.0 JSON schema that the importer reads. Unknown fields are ignored.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Document {
+    #[serde(default)]
+    pub extensions_used: Vec<String>,
+    #[serde(default)]
+    pub extensions_required: Vec<String>,
+    #[serde(default)]
+    pub scene: Option<usize>,
+    #[serde(default)]
+    pub scenes: Vec<Scene>,
+    #[serde(default)]
+    pub nodes: Vec<Node>,
+    #[serde(default)]
+    pub meshes: Vec<Mesh>,
+    #[serde(default)]
+    pub materials: Vec<Material>,
+    #[serde(default)]
+    pub textures: Vec<Texture>,
+    #[serde(default)]
+    pub images: Vec<Image>,
+    #[serde(default)]
+    pub cameras: Vec<Camera>,
+    #[serde(default)]
+    pub accessors: Vec<Accessor>,
+    #[serde(default)]
+    pub buffer_views: Vec<BufferView>,
+    #[serde(default)]
+    pub buffers: Vec<Buffer>,
+    #[serde(default)]
+    pub extensions: Extensions,
+}
+
+#[derive(Deserialize, Default)]
+pub struct Extensions {
+    #[serde(rename = "KHR_lights_punctual")]
+    pub lights_punctual: Option<LightsPunctual>,
+}
+
+#[derive(Deserialize)]
+pub struct LightsPunctual {
+    #[serde(default)]
+    pub lights: Vec<Light>,
+}
+
+#[derive(Deserialize)]
+pub struct Scene {
+    #[serde(default)]
+    pub nodes: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct Node {
+    pub name: Option<String>,
+    #[serde(default)]
+    pub children: Vec<usize>,
+    pub matrix: Option<[f64; 16]>,
+    pub translation: Option<[f64; 3]>,
+    /// Unit quaternion as (x, y, z, w).
+    pub rotation: Option<[f64; 4]>,
+    pub scale: Option<[f64; 3]>,
+    pub mesh: Option<usize>,
+    pub camera: Option<usize>,
+    pub skin: Option<usize>,
+    #[serde(default)]
+    pub extensions: NodeExtensions,
+}
+
+#[derive(Deserialize, Default)]
+pub struct NodeExtensions {
+    #[serde(rename = "KHR_lights_punctual")]
+    pub light: Option<NodeLight>,
+}
+
+#[derive(Deserialize)]
+pub struct NodeLight {
+    pub light: usize,
+}
+
+#[derive(Deserialize)]
+pub struct Mesh {
+    pub primitives: Vec<Primitive>,
+}
+
+#[derive(Deserialize)]
+pub struct Primitive {
+    pub attributes: HashMap<String, usize>,
+    pub indices: Option<usize>,
+    pub material: Option<usize>,
+    #[serde(default = "default_mode")]
+    pub mode: u32,
+    #[serde(default)]
+    pub targets: Vec<serde_json::Value>,
+}
+
+/// Triangle lists.
+pub const TRIANGLES: u32 = 4;
+
+fn default_mode() -> u32 {
+    TRIANGLES
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Material {
+    pub name: Option<String>,
+    #[serde(default)]
+    pub pbr_metallic_roughness: PbrMetallicRoughness,
+    pub normal_texture: Option<TextureInfo>,
+    pub occlusion_texture: Option<TextureInfo>,
+    pub emissive_texture: Option<TextureInfo>,
+    #[serde(default)]
+    pub emissive_factor: [f64; 3],
+    #[serde(default)]
+    pub extensions: MaterialExtensions,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PbrMetallicRoughness {
+    #[serde(default = "default_base_color")]
+    pub base_color_factor: [f64; 4],
+    pub base_color_texture: Option<TextureInfo>,
+    #[serde(default = "default_factor")]
+    pub metallic_factor: f64,
+    #[serde(default = "default_factor")]
+    pub roughness_factor: f64,
+    pub metallic_roughness_texture: Option<TextureInfo>,
+}
+
+impl Default for PbrMetallicRoughness {
+    fn default() -> Self {
+        Self {
+            base_color_factor: default_base_color(),
+            base_color_texture: None,
+            metallic_factor: default_factor(),
+            roughness_factor: default_factor(),
+            metallic_roughness_texture: None,
+        }
+    }
+}
+
+fn default_base_color() -> [f64; 4] {
+    [1.0; 4]
+}
+
+fn default_factor() -> f64 {
+    1.0
+}
+
+#[derive(Deserialize, Default)]
+pub struct MaterialExtensions {
+    #[serde(rename = "KHR_materials_emissive_strength")]
+    pub emissive_strength: Option<EmissiveStrength>,
+    #[serde(rename = "KHR_materials_transmission")]
+    pub transmission: Option<Transmission>,
+    #[serde(rename = "KHR_materials_ior")]
+    pub ior: Option<Ior>,
+    #[serde(rename = "KHR_materials_clearcoat")]
+    pub clearcoat: Option<Clearcoat>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmissiveStrength {
+    #[serde(default = "default_factor")]
+    pub emissive_strength: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transmission {
+    #[serde(default)]
+    pub transmission_factor: f64,
+}
+
+#[derive(Deserialize)]
+pub struct Ior {
+    #[serde(default = "default_ior")]
+    pub ior: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Clearcoat {
+    #[serde(default)]
+    pub clearcoat_factor: f64,
+    #[serde(default)]
+    pub clearcoat_roughness_factor: f64,
+}
+
+pub fn default_ior() -> f64 {
+    1.5
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextureInfo {
+    pub index: usize,
+    #[serde(default)]
+    pub tex_coord: usize,
+}
+
+#[derive(Deserialize)]
+pub struct Texture {
+    pub source: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Image {
+    pub uri: Option<String>,
+    pub buffer_view: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct Camera {
+    pub name: Option<String>,
+    pub perspective: Option<Perspective>,
+    pub orthographic: Option<Orthographic>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Perspective {
+    pub yfov: f64,
+    pub aspect_ratio: Option<f64>,
+}
+
+#[derive(Deserialize)]
+pub struct Orthographic {
+    pub xmag: f64,
+    pub ymag: f64,
+}
+
+#[derive(Deserialize)]
+pub struct Light {
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default = "default_light_color")]
+    pub color: [f64; 3],
+    #[serde(default = "default_factor")]
+    pub intensity: f64,
+}
+
+fn default_light_color() -> [f64; 3] {
+    [1.0; 3]
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Accessor {
+    pub buffer_view: Option<usize>,
+    #[serde(default)]
+    pub byte_offset: usize,
+    pub component_type: u32,
+    #[serde(default)]
+    pub normalized: bool,
+    pub count: usize,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub sparse: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BufferView {
+    pub buffer: usize,
+    #[serde(default)]
+    pub byte_offset: usize,
+    pub byte_length: usize,
+    pub byte_stride: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct Buffer {
+    pub uri: Option<String>,
+}
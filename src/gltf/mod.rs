@@ -0,0 +1,380 @@
+mod buffers;
+mod document;
+
+use std::{collections::{HashMap, HashSet}, f64::consts::PI, fs, path::{Path, PathBuf}, sync::Arc};
+
+use anyhow::{anyhow, bail, ensure, Context};
+use bvh::aabb::AABB;
+use nalgebra_glm::{DMat4, DQuat, DVec2, DVec3, DVec4};
+use nalgebra_glm as glm;
+
+use crate::camera::{Camera, Projection, Screen, View};
+use crate::material::{Diffuse, ImageTexture, Light, Material, Principled};
+use crate::shape::{Group, Instance, Shape, Sphere, TriangleMesh};
+use crate::utils::{NamedCamera, SceneInfo, MAIN_CAMERA};
+use buffers::{load_uri, percent_decode, split_glb, Buffers};
+use document::Document;
+
+/// Extensions that are at least partly understood.
+const SUPPORTED_EXTENSIONS: &[&str] = &[
+    "KHR_lights_punctual",
+    "KHR_materials_emissive_strength",
+    "KHR_materials_transmission",
+    "KHR_materials_ior",
+    "KHR_materials_clearcoat",
+];
+
+/// Height in pixels of imported cameras; the width follows their aspect ratio.
+const SCREEN_HEIGHT: u32 = 300;
+
+pub fn is_gltf_file(path: &Path) -> bool {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+    extension == "gltf" || extension == "glb"
+}
+
+/// Build a scene from a .gltf or .glb file: meshes of the default scene with their node
+/// transforms, metallic-roughness materials, cameras and punctual lights.
+/// Features that cannot be represented are reported as warnings and left out.
+pub fn load_gltf(path: &Path) -> anyhow::Result<SceneInfo> {
+    let bytes = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let (json, bin) = if bytes.starts_with(b"glTF") { split_glb(&bytes)? } else { (&bytes[..], None) };
+    let document: Document = serde_json::from_slice(json).with_context(|| format!("failed to parse {}", path.display()))?;
+
+    for extension in document.extensions_used.iter().filter(|e| !SUPPORTED_EXTENSIONS.contains(&e.as_str())) {
+        let required = if document.extensions_required.contains(extension) { "required " } else { "" };
+        warn(format!("{}extension {} is not supported", required, extension));
+    }
+
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    let data = document.buffers.iter().enumerate().map(|(i, buffer)| match (&buffer.uri, bin) {
+        (Some(uri), _) => load_uri(uri, base_dir),
+        (None, Some(bin)) if i == 0 => Ok(bin.to_vec()),
+        (None, _) => bail!("buffer {} has no data", i),
+    }).collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut importer = Importer {
+        buffers: Buffers { document: &document, data },
+        base_dir,
+        file_name: path.file_name().map(PathBuf::from).unwrap_or_default(),
+        materials: vec![],
+        default_material: Arc::new(Diffuse::new(DVec3::repeat(0.8))),
+        meshes: HashMap::new(),
+        shapes: vec![],
+        cameras: vec![],
+        visited_nodes: HashSet::new(),
+        lights: vec![],
+    };
+    importer.materials = document.materials.iter().enumerate().map(|(i, m)| importer.material(i, m)).collect();
+
+    let scene_index = document.scene.unwrap_or(0);
+    if let Some(scene) = document.scenes.get(scene_index) {
+        for &node in &scene.nodes {
+            importer.node(node, &DMat4::identity())?;
+        }
+    } else {
+        // without scenes, every root node is shown
+        let children = document.nodes.iter().flat_map(|n| n.children.iter().copied()).collect::<Vec<_>>();
+        for node in (0..document.nodes.len()).filter(|n| !children.contains(n)) {
+            importer.node(node, &DMat4::identity())?;
+        }
+    }
+    Ok(importer.into_scene())
+}
+
+fn warn(message: String) {
+    eprintln!("warning: glTF: {}", message);
+}
+
+fn vec3(v: &[f64]) -> DVec3 {
+    DVec3::new(v[0], v[1], v[2])
+}
+
+struct Importer<'a> {
+    buffers: Buffers<'a>,
+    base_dir: &'a Path,
+    /// Names images embedded in the file.
+    file_name: PathBuf,
+    materials: Vec<Arc<dyn Material>>,
+    default_material: Arc<dyn Material>,
+    /// Shapes by glTF mesh, shared by all nodes using the mesh.
+    meshes: HashMap<usize, Option<Arc<dyn Shape>>>,
+    shapes: Vec<Arc<dyn Shape>>,
+    cameras: Vec<NamedCamera>,
+    visited_nodes: HashSet<usize>,
+    /// Point lights by position.
+    lights: Vec<(DVec3, usize, &'a document::Light)>,
+}
+
+impl<'a> Importer<'a> {
+    fn document(&self) -> &'a Document {
+        self.buffers.document
+    }
+
+    fn node(&mut self, index: usize, parent: &DMat4) -> anyhow::Result<()> {
+        let node = self.document().nodes.get(index).ok_or_else(|| anyhow!("missing node {}", index))?;
+        // nodes form a forest, so reaching one twice means a cycle or a shared child
+        ensure!(self.visited_nodes.insert(index), "node {} is reached more than once, the node hierarchy is not a tree", index);
+        let local = match node.matrix {
+            Some(matrix) => DMat4::from_column_slice(&matrix),
+            None => {
+                let [x, y, z, w] = node.rotation.unwrap_or([0.0, 0.0, 0.0, 1.0]);
+                glm::translation(&DVec3::from(node.translation.unwrap_or([0.0; 3])))
+                    * glm::quat_to_mat4(&DQuat::new(w, x, y, z))
+                    * glm::scaling(&DVec3::from(node.scale.unwrap_or([1.0; 3])))
+            }
+        };
+        let transform = parent * local;
+
+        if node.skin.is_some() {
+            warn(format!("skin of node {} is ignored", index));
+        }
+        if let Some(mesh) = node.mesh {
+            if let Some(shape) = self.mesh(mesh)? {
+                self.shapes.push(Arc::new(Instance::new(shape, transform)));
+            }
+        }
+        if let Some(camera) = node.camera {
+            let name = node.name.clone().unwrap_or_else(|| format!("node{}", index));
+            self.camera(camera, name, &transform)?;
+        }
+        if let Some(ref light) = node.extensions.light {
+            let index = light.light;
+            let light = self.document().extensions.lights_punctual.as_ref()
+                .and_then(|l| l.lights.get(index))
+                .ok_or_else(|| anyhow!("missing light {}", index))?;
+            self.lights.push(((transform * DVec4::new(0.0, 0.0, 0.0, 1.0)).xyz(), index, light));
+        }
+        for &child in &node.children {
+            self.node(child, &transform)?;
+        }
+        Ok(())
+    }
+
+    fn mesh(&mut self, index: usize) -> anyhow::Result<Option<Arc<dyn Shape>>> {
+        if let Some(shape) = self.meshes.get(&index) {
+            return Ok(shape.clone());
+        }
+        let mesh = self.document().meshes.get(index).ok_or_else(|| anyhow!("missing mesh {}", index))?;
+        let mut primitives = vec![];
+        for (i, primitive) in mesh.primitives.iter().enumerate() {
+            if primitive.mode != document::TRIANGLES {
+                warn(format!("primitive {} of mesh {} is not a triangle list and is skipped", i, index));
+                continue;
+            }
+            if !primitive.targets.is_empty() {
+                warn(format!("morph targets of mesh {} are ignored", index));
+            }
+            match self.primitive(primitive, &format!("primitive {} of mesh {}", i, index)) {
+                Ok(primitive) => primitives.push(Arc::new(primitive) as Arc<dyn Shape>),
+                Err(e) => warn(format!("primitive {} of mesh {} is skipped: {:#}", i, index, e)),
+            }
+        }
+        let shape = match primitives.len() {
+            0 => None,
+            1 => primitives.pop(),
+            _ => Some(Arc::new(Group::new(primitives)) as Arc<dyn Shape>),
+        };
+        self.meshes.insert(index, shape.clone());
+        Ok(shape)
+    }
+
+    /// Values of the accessor, if its type is one of `kinds`.
+    fn read_accessor(&self, accessor: usize, kinds: &[&str]) -> anyhow::Result<Vec<Vec<f64>>> {
+        let kind = &self.document().accessors.get(accessor).ok_or_else(|| anyhow!("missing accessor {}", accessor))?.kind;
+        ensure!(kinds.contains(&kind.as_str()), "accessor {} is {}, expected {}", accessor, kind, kinds.join(" or "));
+        self.buffers.read(accessor)
+    }
+
+    /// `name` is used in warnings about attributes that are dropped.
+    fn primitive(&self, primitive: &document::Primitive, name: &str) -> anyhow::Result<TriangleMesh> {
+        let positions = match primitive.attributes.get("POSITION") {
+            Some(&accessor) => self.read_accessor(accessor, &["VEC3"])?.iter().map(|p| vec3(p)).collect::<Vec<_>>(),
+            None => bail!("primitive has no positions"),
+        };
+        // optional attributes of the wrong type or count are left out rather than failing the primitive
+        let attribute = |attribute: &str, kinds: &[&str]| -> Vec<Vec<f64>> {
+            let Some(&accessor) = primitive.attributes.get(attribute) else {
+                return vec![];
+            };
+            match self.read_accessor(accessor, kinds) {
+                Ok(values) if values.len() == positions.len() => values,
+                Ok(values) => {
+                    warn(format!("{} of {} has {} values for {} positions and is ignored", attribute, name, values.len(), positions.len()));
+                    vec![]
+                }
+                Err(e) => {
+                    warn(format!("{} of {} is ignored: {:#}", attribute, name, e));
+                    vec![]
+                }
+            }
+        };
+        let normals = attribute("NORMAL", &["VEC3"]).iter().map(|n| vec3(n)).collect();
+        // glTF puts the origin of texture space at the top left
+        let uvs = attribute("TEXCOORD_0", &["VEC2"]).iter().map(|t| DVec2::new(t[0], 1.0 - t[1])).collect();
+        let colors = attribute("COLOR_0", &["VEC3", "VEC4"]).iter().map(|c| vec3(c)).collect();
+        let indices = match primitive.indices {
+            Some(accessor) => self.read_accessor(accessor, &["SCALAR"])?.iter().map(|i| i[0] as usize).collect(),
+            None => (0..positions.len()).collect::<Vec<_>>(),
+        };
+        if indices.iter().any(|&i| i >= positions.len()) {
+            bail!("primitive refers to a missing vertex");
+        }
+        let material = match primitive.material {
+            Some(material) => self.materials.get(material).cloned().ok_or_else(|| anyhow!("missing material {}", material))?,
+            None => self.default_material.clone(),
+        };
+        let indices = indices.chunks_exact(3).map(|f| [f[0], f[1], f[2]]).collect();
+        TriangleMesh::new(positions, normals, uvs, indices, material)?.with_colors(colors)
+    }
+
+    /// Emissive surfaces become `Light`, and the rest `Principled` with the metallic-roughness
+    /// parameters, transmission, IOR and clearcoat of the material.
+    /// Only the base color texture is read; the others are reported and ignored.
+    fn material(&self, index: usize, material: &document::Material) -> Arc<dyn Material> {
+        let name = material.name.clone().unwrap_or_else(|| format!("#{}", index));
+        let pbr = &material.pbr_metallic_roughness;
+        let base_color = vec3(&pbr.base_color_factor);
+        for (texture, kind) in [
+            (&material.normal_texture, "normal"),
+            (&material.occlusion_texture, "occlusion"),
+            (&material.emissive_texture, "emissive"),
+            (&pbr.metallic_roughness_texture, "metallic-roughness"),
+        ] {
+            if texture.is_some() {
+                warn(format!("{} texture of material {} is ignored", kind, name));
+            }
+        }
+
+        let strength = material.extensions.emissive_strength.as_ref().map_or(1.0, |e| e.emissive_strength);
+        let emissive = vec3(&material.emissive_factor) * strength;
+        if emissive.max() > 0.0 {
+            return Arc::new(Light::new(emissive / emissive.max(), emissive.max()));
+        }
+        let extensions = &material.extensions;
+        let mut principled = Principled {
+            metallic: pbr.metallic_factor,
+            roughness: pbr.roughness_factor,
+            transmission: extensions.transmission.as_ref().map_or(0.0, |t| t.transmission_factor),
+            ior: extensions.ior.as_ref().map_or(document::default_ior(), |i| i.ior),
+            ..Principled::new(base_color)
+        };
+        if let Some(ref clearcoat) = extensions.clearcoat {
+            principled.clearcoat = clearcoat.clearcoat_factor;
+            principled.clearcoat_gloss = 1.0 - clearcoat.clearcoat_roughness_factor;
+        }
+        if let Some(ref info) = pbr.base_color_texture {
+            if info.tex_coord != 0 {
+                warn(format!("material {} uses texture coordinates {}, only the first set is read", name, info.tex_coord));
+            }
+            match self.texture(info.index) {
+                Ok(texture) => principled.texture = Some(texture),
+                Err(e) => warn(format!("base color texture of material {}: {:#}", name, e)),
+            }
+        }
+        Arc::new(principled)
+    }
+
+    fn texture(&self, index: usize) -> anyhow::Result<ImageTexture> {
+        let document = self.document();
+        let source = document.textures.get(index).and_then(|t| t.source)
+            .ok_or_else(|| anyhow!("texture {} has no image", index))?;
+        let image = document.images.get(source).ok_or_else(|| anyhow!("missing image {}", source))?;
+        let embedded_name = || self.file_name.with_extension(format!("image{}", source));
+        match (&image.uri, image.buffer_view) {
+            (Some(uri), _) if !uri.starts_with("data:") => ImageTexture::load(self.base_dir.join(percent_decode(uri)?)),
+            (Some(uri), _) => Ok(ImageTexture::from_image(embedded_name(), image::load_from_memory(&load_uri(uri, self.base_dir)?)?)),
+            (None, Some(view)) => Ok(ImageTexture::from_image(embedded_name(), image::load_from_memory(self.buffers.view(view)?)?)),
+            (None, None) => bail!("image {} has no data", source),
+        }
+    }
+
+    fn camera(&mut self, index: usize, node_name: String, transform: &DMat4) -> anyhow::Result<()> {
+        let camera = self.document().cameras.get(index).ok_or_else(|| anyhow!("missing camera {}", index))?;
+        let eye = (transform * DVec4::new(0.0, 0.0, 0.0, 1.0)).xyz();
+        let forward = (transform * DVec4::new(0.0, 0.0, -1.0, 0.0)).xyz().normalize();
+        let up = (transform * DVec4::new(0.0, 1.0, 0.0, 0.0)).xyz().normalize();
+
+        let (projection, vfov, aspect_ratio) = match (&camera.perspective, &camera.orthographic) {
+            (Some(perspective), _) => (Projection::Perspective, perspective.yfov.to_degrees(), perspective.aspect_ratio),
+            // the viewport of an orthographic camera one unit from its target spans 2 * ymag
+            (None, Some(orthographic)) => (
+                Projection::Orthographic,
+                2.0 * orthographic.ymag.atan().to_degrees(),
+                Some(orthographic.xmag / orthographic.ymag),
+            ),
+            (None, None) => bail!("camera {} is neither perspective nor orthographic", index),
+        };
+        let aspect_ratio = aspect_ratio.unwrap_or(4.0 / 3.0);
+        let camera = Camera {
+            screen: Screen::new((SCREEN_HEIGHT as f64 * aspect_ratio).round() as u32, SCREEN_HEIGHT),
+            view: View::LookAt { eye, target: eye + forward, up, vfov },
+            projection,
+            ..Default::default()
+        };
+        let name = self.document().cameras[index].name.clone().unwrap_or(node_name);
+        let is_taken = |name: &str| name == MAIN_CAMERA || self.cameras.iter().any(|c| c.name == name);
+        let name = if is_taken(&name) {
+            let unique = (2..).map(|i| format!("{}_{}", name, i)).find(|n| !is_taken(n)).unwrap();
+            warn(format!("camera name {} is taken, renamed to {}", name, unique));
+            unique
+        } else {
+            name
+        };
+        self.cameras.push(NamedCamera { name, camera, interocular_distance: None });
+        Ok(())
+    }
+
+    fn into_scene(mut self) -> SceneInfo {
+        let bounds = self.shapes.iter().fold(AABB::empty(), |aabb, s| aabb.join(&s.aabb()));
+        let (center, size) = if self.shapes.is_empty() {
+            (DVec3::zeros(), 1.0)
+        } else {
+            let (min, max) = (bounds.min, bounds.max);
+            let (min, max) = (DVec3::new(min.x as f64, min.y as f64, min.z as f64), DVec3::new(max.x as f64, max.y as f64, max.z as f64));
+            (0.5 * (min + max), (max - min).norm().max(1e-3))
+        };
+
+        // Only surfaces emit light, so point lights become small glowing spheres
+        // with the same intensity.
+        let light_radius = 0.005 * size;
+        let mut spheres = vec![];
+        for &(position, index, light) in &self.lights {
+            let name = light.name.clone().unwrap_or_else(|| format!("#{}", index));
+            match light.kind.as_str() {
+                "point" | "spot" => {
+                    if light.kind == "spot" {
+                        warn(format!("spot light {} shines in all directions", name));
+                    }
+                    let radiance = light.intensity / (PI * light_radius * light_radius);
+                    spheres.push(Sphere::new(position, light_radius, Arc::new(Light::new(vec3(&light.color), radiance))));
+                }
+                other => warn(format!("{} light {} is not supported", other, name)),
+            }
+        }
+
+        let camera = if self.cameras.is_empty() {
+            warn("no camera, framing the whole scene".to_string());
+            let vfov: f64 = 45.0;
+            let distance = 0.5 * size / (0.5 * vfov.to_radians()).sin();
+            Camera {
+                screen: Screen::new(400, SCREEN_HEIGHT),
+                view: View::LookAt { eye: center + DVec3::new(0.0, 0.0, distance), target: center, up: DVec3::y(), vfov },
+                ..Default::default()
+            }
+        } else {
+            self.cameras.remove(0).camera
+        };
+
+        SceneInfo {
+            camera,
+            spheres,
+            cubes: vec![],
+            bunnies: vec![],
+            meshes: vec![],
+            shapes: self.shapes,
+            cameras: self.cameras,
+            cornell_box: false,
+            ..SceneInfo::default()
+        }
+    }
+}
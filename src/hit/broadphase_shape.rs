@@ -1,7 +1,5 @@
 use bvh::bounding_hierarchy::BHShape;
 
-use bvh;
-
 use bvh::aabb::Bounded;
 
 use crate::shape::Shape;
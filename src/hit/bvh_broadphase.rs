@@ -3,15 +3,28 @@ use bvh::bvh::BVH;
 
 #[derive(Default)]
 pub struct BVHBroadPhase {
-  bvh: Option<BVH>
+  bvh: Option<BVH>,
+  /// Shapes before this index are in the BVH. The rest are unbounded and always returned.
+  bounded_count: usize,
 }
 
 impl BroadPhase for BVHBroadPhase {
     fn trace<'a>(&'a self, shapes: &'a [BroadPhaseShape], ray: &ray::Ray) -> Vec<&'a BroadPhaseShape> {
-        self.bvh.as_ref().expect("BVHBroadPhase not initialized").traverse(&ray.into(), shapes).into_iter().map(|x| x.to_owned()).collect()
+        let (bounded, unbounded) = shapes.split_at(self.bounded_count);
+        let candidates = if bounded.is_empty() {
+            vec![]
+        } else {
+            self.bvh.as_ref().expect("BVHBroadPhase not initialized").traverse(&ray.into(), bounded)
+        };
+        candidates.into_iter().chain(unbounded.iter()).collect()
     }
 
+    /// Moves unbounded shapes to the end of `shapes` and builds the BVH over the others.
     fn build(&mut self, shapes: &mut [BroadPhaseShape]) {
-        self.bvh = Some(BVH::build(shapes));
+        shapes.sort_by_key(|s| !s.shape.is_bounded());
+        self.bounded_count = shapes.iter().take_while(|s| s.shape.is_bounded()).count();
+        if self.bounded_count > 0 {
+            self.bvh = Some(BVH::build(&mut shapes[..self.bounded_count]));
+        }
     }
-}
\ No newline at end of file
+}
@@ -7,24 +7,43 @@ mod ray;
 
 
 
+use std::sync::Arc;
+
 use nalgebra_glm as glm;
 
+use crate::material::Material;
+
 pub use bvh_broadphase::BVHBroadPhase;
 pub use noop_broadphase::NoOpBroadPhase;
 pub use ray::Ray;
 pub use broadphase_shape::BroadPhaseShape;
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub struct HitRecord {
     pub toi: f64,
     pub point: glm::TVec3<f64>,
     pub normal: glm::TVec3<f64>,
+    /// Texture coordinates of the hit point, for shapes that have them.
+    pub uv: Option<glm::DVec2>,
+    /// Vertex color of the hit point, for meshes that have them.
+    pub color: Option<glm::DVec3>,
+    /// Unit direction along fiber-like shapes such as curves, for anisotropic materials.
+    pub tangent: Option<glm::DVec3>,
+    /// Material of the part that was hit, set by shapes composed of parts with their own materials.
+    pub material: Option<Arc<dyn Material>>,
+}
+
+impl HitRecord {
+    pub fn new(toi: f64, point: glm::TVec3<f64>, normal: glm::TVec3<f64>) -> Self {
+        Self { toi, point, normal, uv: None, color: None, tangent: None, material: None }
+    }
 }
 
 pub trait BroadPhase: Sync + Send {
     /// return shapes that can *possibly* intersect with the ray.
     fn trace<'a>(&'a self, shapes: &'a [BroadPhaseShape], ray: &ray::Ray) -> Vec<&'a BroadPhaseShape>;
 
-    /// build the underlying data structure of broad phase
+    /// build the underlying data structure of broad phase.
+    /// `shapes` may be reordered; later calls to `trace` must pass them in the new order.
     fn build(&mut self, shapes: &mut [BroadPhaseShape]);
 }
\ No newline at end of file
@@ -8,7 +8,7 @@ pub struct NoOpBroadPhase;
 
 impl BroadPhase for NoOpBroadPhase {
     fn trace<'a>(&'a self, shapes: &'a [BroadPhaseShape], _ray: &ray::Ray) -> Vec<&'a BroadPhaseShape> {
-      shapes.iter().map(|x| x).collect_vec()
+      shapes.iter().collect_vec()
     }
 
     fn build(&mut self, shapes: &mut [BroadPhaseShape]) {
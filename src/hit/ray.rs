@@ -1,23 +1,29 @@
-use bvh;
-
 use nalgebra_glm as glm;
 use glm::DVec3;
 
 pub struct Ray {
     pub origin: DVec3,
-    pub direction: DVec3
+    pub direction: DVec3,
+    /// Time at which the ray is cast. Shapes with motion are at their start pose at time 0
+    /// and at their end pose at time 1.
+    pub time: f64
 }
 
 impl Ray {
     pub fn new(origin: DVec3, direction: DVec3) -> Self {
-        Self { origin, direction: direction.normalize() }
+        Self { origin, direction: direction.normalize(), time: 0.0 }
+    }
+
+    pub fn with_time(self, time: f64) -> Self {
+        Self { time, ..self }
     }
 }
 
 impl From<&bvh::ray::Ray> for Ray {
     fn from(ray: &bvh::ray::Ray) -> Self {
         Self { origin: DVec3::new(ray.origin.x as f64, ray.origin.y as f64, ray.origin.z as f64),
-            direction: DVec3::new(ray.direction.x as f64, ray.direction.y as f64, ray.direction.z as f64) }
+            direction: DVec3::new(ray.direction.x as f64, ray.direction.y as f64, ray.direction.z as f64),
+            time: 0.0 }
     }
 }
 
@@ -27,17 +33,17 @@ impl From<bvh::ray::Ray> for Ray {
     }
 }
 
-impl Into<bvh::ray::Ray> for Ray {
-    fn into(self) -> bvh::ray::Ray {
-        (&self).into()
+impl From<Ray> for bvh::ray::Ray {
+    fn from(val: Ray) -> Self {
+        (&val).into()
     }
 }
 
-impl Into<bvh::ray::Ray> for &Ray {
-    fn into(self) -> bvh::ray::Ray {
+impl From<&Ray> for bvh::ray::Ray {
+    fn from(val: &Ray) -> Self {
         bvh::ray::Ray::new(
-            bvh::Point3::new(self.origin.x as f32, self.origin.y as f32, self.origin.z as f32), 
-            bvh::Vector3::new(self.direction.x as f32, self.direction.y as f32, self.direction.z as f32), 
+            bvh::Point3::new(val.origin.x as f32, val.origin.y as f32, val.origin.z as f32), 
+            bvh::Vector3::new(val.direction.x as f32, val.direction.y as f32, val.direction.z as f32), 
         )
     }
 }
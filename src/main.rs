@@ -1,4 +1,4 @@
-use std::{sync::Arc, collections::HashMap, fs::File, io::BufReader};
+use std::{sync::Arc, collections::HashMap, fs::File, io::BufReader, path::Path};
 
 use glm::{DVec3};
 use hit::{BroadPhase, BroadPhaseShape, BVHBroadPhase, NoOpBroadPhase};
@@ -7,24 +7,27 @@ use rand::prelude::*;
 use kdam::{tqdm};
 
 mod camera;
+mod gltf;
 mod hit;
 mod shape;
 mod tracer;
 mod utils;
 mod material;
+mod medium;
 
 use nalgebra_glm as glm;
 use rayon::prelude::*;
+use camera::{Camera, Region};
 use shape::{Shape};
 use tracer::{TracingHelper};
-use utils::{cornell_box, SceneInfo};
-use clap::{Parser, arg, command};
+use utils::{cornell_box, format_camera_path, format_frame_path, with_scene_dir, MeshCache, SceneInfo, BLACK, MAIN_CAMERA};
+use clap::Parser;
 
 /// A Simple PBR ray tracer
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Args {
-    /// Scene file. If not given, the default scene will be used
+    /// Scene file, or a .gltf/.glb file to import. If not given, the default scene will be used
     #[arg(short = 'i', long)]
     scene_file: Option<String>,
 
@@ -44,9 +47,36 @@ struct Args {
     #[arg(short = 'b', long, default_value_t = false)]
     skip_bvh: bool,
 
-    /// Output file.
+    /// Output file. `%d` or `%04d` in the name is replaced with the frame number.
     #[arg(short = 'o', long, default_value = "output.png")]
-    output_file: String
+    output_file: String,
+
+    /// First frame to render. Frame n shows the scene at time n / fps.
+    #[arg(long)]
+    frame_start: Option<u32>,
+
+    /// Last frame to render, inclusive. Defaults to the first frame.
+    #[arg(long)]
+    frame_end: Option<u32>,
+
+    /// Only trace the window x0,y0,x1,y1 of the image, in pixels from the top left corner.
+    /// Overrides the region in the scene file
+    #[arg(long, value_name = "x0,y0,x1,y1")]
+    region: Option<Region>,
+
+    /// Write only the traced region instead of a full-size image with the rest black
+    #[arg(long, default_value_t = false)]
+    crop: bool,
+
+    /// Camera to render, by name. Can be given several times.
+    /// `main` is the scene's `camera`; stereo cameras are named `<name>_left` and `<name>_right`.
+    /// With several cameras, `%c` in the output file is replaced with the camera name
+    #[arg(short = 'c', long = "camera", value_name = "NAME")]
+    cameras: Vec<String>,
+
+    /// Render all cameras of the scene
+    #[arg(long, default_value_t = false)]
+    all_cameras: bool,
 }
 
 
@@ -57,33 +87,93 @@ fn main() -> anyhow::Result<()> {
         println!("{}", serde_json::to_string_pretty(&scene).unwrap());
         return Ok(())
     }
+    // paths in the scene file are relative to it
+    let base_dir = args.scene_file.as_ref()
+        .and_then(|path| Path::new(path).parent())
+        .unwrap_or(Path::new(""));
+
     if let Some(ref path) = args.scene_file {
-        let f = File::open(path)?;
-        scene = serde_json::from_reader(BufReader::new(f))?;
+        if gltf::is_gltf_file(Path::new(path)) {
+            scene = gltf::load_gltf(Path::new(path))?;
+        } else {
+            let f = File::open(path)?;
+            scene = with_scene_dir(base_dir, || serde_json::from_reader(BufReader::new(f)))?;
+        }
+        scene.validate()?;
+    }
+
+    let region = args.region.or(scene.region).map(|r| Region { crop: r.crop || args.crop, ..r });
+    anyhow::ensure!(!args.crop || region.is_some(), "--crop needs a region, given by --region or in the scene file");
+
+    let frame_start = args.frame_start.unwrap_or(0);
+    let frame_end = args.frame_end.unwrap_or(frame_start);
+    anyhow::ensure!(frame_end >= frame_start, "last frame {} comes before first frame {}", frame_end, frame_start);
+    let is_sequence = frame_end > frame_start;
+
+    let view_names = scene.views().into_iter().map(|(name, _)| name).collect_vec();
+    for name in &args.cameras {
+        anyhow::ensure!(view_names.contains(name), "unknown camera {}, expected one of {:?}", name, view_names);
+    }
+    let is_selected = |name: &String| {
+        args.all_cameras || args.cameras.contains(name) || (args.cameras.is_empty() && name == MAIN_CAMERA)
+    };
+    let is_multi_view = view_names.iter().filter(|name| is_selected(name)).count() > 1;
+
+    let mut mesh_cache = MeshCache::default();
+    let mut world = None;
+    for frame in frame_start..=frame_end {
+        let frame_scene = scene.at_time(frame as f64 / scene.fps);
+        if world.is_none() || scene.has_animated_models() {
+            world = Some(build_world(&frame_scene, base_dir, &mut mesh_cache, args.skip_bvh)?);
+        }
+        let (obj, broad_phase) = world.as_ref().unwrap();
+        let tracing_helper = TracingHelper::new(obj, broad_phase.as_ref(), args.depth_limit, &frame_scene.volumes, frame_scene.fog.as_deref());
+
+        for (name, camera) in frame_scene.views().into_iter().filter(|(name, _)| is_selected(name)) {
+            if let Some(ref region) = region {
+                region.validate(&camera.screen)?;
+            }
+            let output = render(&camera, &tracing_helper, args.samples_per_pixel, region.as_ref());
+            let conv = image::DynamicImage::ImageRgb32F(output).into_rgb8();
+            let path = format_camera_path(&args.output_file, &name, is_multi_view);
+            conv.save(format_frame_path(&path, frame, is_sequence))?;
+        }
     }
+    Ok(())
+}
 
-    let screen = &scene.camera.screen;
-    let camera = &scene.camera;
-    let mut output = image::Rgb32FImage::new(screen.width, screen.height);
-    let mut world: Vec<Arc<dyn Shape>> = cornell_box();
+fn build_world(scene: &SceneInfo, base_dir: &Path, mesh_cache: &mut MeshCache, skip_bvh: bool) -> anyhow::Result<(Vec<BroadPhaseShape>, Box<dyn BroadPhase>)> {
+    let mut world: Vec<Arc<dyn Shape>> = if scene.cornell_box { cornell_box() } else { vec![] };
 
-    world.append(&mut scene.split_to_shape());
+    world.append(&mut scene.split_to_shape(base_dir, mesh_cache)?);
 
     let mut obj: Vec<BroadPhaseShape> = world
         .iter()
         .map(|s| BroadPhaseShape::new(s.clone()))
         .collect();
     
-    let mut broad_phase: Box<dyn BroadPhase> = if args.skip_bvh {
-        Box::new(NoOpBroadPhase::default())
+    let mut broad_phase: Box<dyn BroadPhase> = if skip_bvh {
+        Box::new(NoOpBroadPhase)
     } else {
         Box::new(BVHBroadPhase::default())
     };
     broad_phase.build(&mut obj);
-    let tracing_helper = TracingHelper::new(&obj, broad_phase, args.depth_limit);
+    Ok((obj, broad_phase))
+}
+
+fn render(camera: &Camera, tracing_helper: &TracingHelper, samples_per_pixel: i32, region: Option<&Region>) -> image::Rgb32FImage {
+    let screen = &camera.screen;
+    let region = region.copied().unwrap_or(Region { x0: 0, y0: 0, x1: screen.width, y1: screen.height, crop: false });
+    let mut output = if region.crop {
+        image::Rgb32FImage::new(region.width(), region.height())
+    } else {
+        image::Rgb32FImage::new(screen.width, screen.height)
+    };
+
+    let screen_rows = region.screen_rows(screen);
 
     let mut image_map = HashMap::<(u32, u32), DVec3>::new();
-    let res = tqdm!((0..screen.width).cartesian_product(0..screen.height).cartesian_product(0..args.samples_per_pixel))
+    let res = tqdm!((region.x0..region.x1).cartesian_product(screen_rows.clone()).cartesian_product(0..samples_per_pixel))
         .par_bridge()
         .into_par_iter()
         .map(|((x, y), _)| -> (u32, u32, glm::DVec3) {
@@ -91,31 +181,24 @@ fn main() -> anyhow::Result<()> {
             let dist = rand::distributions::Uniform::new(0.0, 1.0f64);
             let u = (x as f64 + dist.sample(&mut rng)) / (screen.width as f64);
             let v = (y as f64 + dist.sample(&mut rng)) / (screen.height as f64);
-            let ray_dir = camera.left_bottom_vec()
-                + u * camera.horizontal_vec()
-                + v * camera.vertical_vec();
-
-            let ray = hit::Ray {
-                origin: camera.origin,
-                direction: ray_dir,
+            let color = match camera.get_ray(u, v) {
+                Some(ray) => tracing_helper.start_trace(&ray),
+                None => BLACK,
             };
-            let color = tracing_helper.start_trace(&ray);
             (x, y, color)
         })
         .collect::<Vec<(u32, u32, DVec3)>>();
     
     for (i, j, color) in res {
-        let k = 1.0 / (args.samples_per_pixel as f64);
+        let k = camera.exposure.scale() / (samples_per_pixel as f64);
         
         *image_map.entry((i, j)).or_insert(DVec3::zeros()) += k * color;
     }
-    for (i, j) in (0..screen.width).cartesian_product(0..screen.height) {
+    for (i, j) in (region.x0..region.x1).cartesian_product(screen_rows) {
         let vec = image_map.get(&(i, j)).unwrap();
         let vec_f32 = [vec.x as f32, vec.y as f32, vec.z as f32];
-        // Coordinate system differs: +y on the screen becomes -y in the picture
-        output.put_pixel(i, screen.height - 1 - j, image::Rgb::<f32>(vec_f32.into()))
+        let (x, y) = region.image_pixel(screen, i, j);
+        output.put_pixel(x, y, image::Rgb::<f32>(vec_f32))
     }
-    let conv = image::DynamicImage::ImageRgb32F(output).into_rgb8();
-    conv.save(args.output_file)?;
-    Ok(())
+    output
 }
@@ -0,0 +1,73 @@
+use nalgebra_glm::DVec3;
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+
+use crate::hit::{HitRecord, Ray};
+
+use super::{Material, microfacet::{Frame, Ggx, fresnel_conductor}};
+
+/// Measured indices of refraction of common metals, for red, green and blue.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum MetalPreset {
+    Gold,
+    Copper,
+    Aluminium,
+    Silver,
+}
+
+/// Complex index of refraction `eta + i k` per color channel, either a preset or given directly.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ComplexIor {
+    Preset(MetalPreset),
+    Custom { eta: DVec3, k: DVec3 },
+}
+
+impl ComplexIor {
+    pub fn eta_k(&self) -> (DVec3, DVec3) {
+        match self {
+            ComplexIor::Preset(MetalPreset::Gold) => (DVec3::new(0.143, 0.374, 1.442), DVec3::new(3.983, 2.385, 1.603)),
+            ComplexIor::Preset(MetalPreset::Copper) => (DVec3::new(0.200, 0.924, 1.102), DVec3::new(3.912, 2.452, 2.142)),
+            ComplexIor::Preset(MetalPreset::Aluminium) => (DVec3::new(1.657, 0.880, 0.521), DVec3::new(9.224, 6.270, 4.837)),
+            ComplexIor::Preset(MetalPreset::Silver) => (DVec3::new(0.155, 0.117, 0.138), DVec3::new(4.828, 3.122, 2.147)),
+            ComplexIor::Custom { eta, k } => (*eta, *k),
+        }
+    }
+}
+
+/// Rough metal with GGX microfacets and the Fresnel reflectance of its complex index of refraction.
+/// Unlike `Metal`, it reflects off both sides.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Conductor {
+    pub ior: ComplexIor,
+    /// From 0 (mirror) to 1.
+    #[serde(default)]
+    pub roughness: f64,
+}
+
+#[typetag::serde]
+impl Material for Conductor {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Vec<(DVec3, Ray)> {
+        let wo_world = -ray.direction.normalize();
+        let normal = if wo_world.dot(&hit.normal) < 0.0 { -hit.normal } else { hit.normal };
+        let frame = Frame::new(&normal);
+        let wo = frame.to_local(&wo_world);
+        if wo.z <= 0.0 {
+            return vec![];
+        }
+        let ggx = Ggx::from_roughness(self.roughness);
+        let mut rng = rand::thread_rng();
+        let m = ggx.sample_visible_normal(&wo, rng.gen(), rng.gen());
+        let wi = 2.0 * wo.dot(&m) * m - wo;
+        if wi.z <= 0.0 {
+            return vec![];
+        }
+        let (eta, k) = self.ior.eta_k();
+        let weight = fresnel_conductor(wo.dot(&m), &eta, &k) * (ggx.g2(&wo, &wi) / ggx.g1(&wo));
+        vec![(weight, Ray::new(hit.point, frame.to_world(&wi)))]
+    }
+
+    fn emit(&self, _ray: &Ray, _hit: &HitRecord) -> DVec3 {
+        DVec3::zeros()
+    }
+}
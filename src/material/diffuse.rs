@@ -3,16 +3,19 @@ use serde::{Serialize, Deserialize};
 
 use crate::{hit::{Ray, HitRecord}, utils::random_in_unit_sphere};
 use nalgebra_glm as glm;
-use super::Material;
+use super::{ImageTexture, Material, textured_color};
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Diffuse {
     pub color_diffuse: DVec3,
+    /// Multiplies `color_diffuse` on shapes with UVs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub texture: Option<ImageTexture>,
 }
 
 impl Diffuse {
     pub fn new(color_diffuse: DVec3) -> Self {
-        Self { color_diffuse }
+        Self { color_diffuse, texture: None }
     }
 }
 
@@ -25,7 +28,7 @@ impl Material for Diffuse {
         }
         let ray_scattered = Ray::new(hit.point, direction);
         vec![
-            (self.color_diffuse, ray_scattered)
+            (textured_color(&self.color_diffuse, &self.texture, hit), ray_scattered)
         ]
     }
     fn emit(&self, _ray: &Ray, _hit: &HitRecord) -> glm::DVec3 {
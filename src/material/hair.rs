@@ -0,0 +1,132 @@
+use std::f64::consts::PI;
+
+use nalgebra_glm::DVec3;
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+
+use crate::{hit::{HitRecord, Ray}, utils::orthonormal_basis};
+
+use super::{Material, microfacet::fresnel_dielectric};
+
+/// Hair fiber scattering after Chiang et al. 2016, for curves: light is reflected off the cuticle (R),
+/// transmitted through the fiber (TT), reflected inside of it once (TRT) or more often.
+/// A lobe is picked by its attenuation and its direction sampled, so no lobe is evaluated on its own.
+/// Shapes without a tangent are treated as fibers lying across the normal, hit in the middle.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Hair {
+    /// Color of the hair after light has passed through it, from which its absorption follows.
+    pub color: DVec3,
+    #[serde(default = "default_eta")]
+    pub eta: f64,
+    /// Longitudinal roughness, from 0 (smooth) to 1.
+    #[serde(default = "default_roughness")]
+    pub beta_m: f64,
+    /// Azimuthal roughness, from 0 (smooth) to 1.
+    #[serde(default = "default_roughness")]
+    pub beta_n: f64,
+    /// Tilt of the cuticle scales in degrees.
+    #[serde(default = "default_alpha")]
+    pub alpha: f64,
+}
+
+fn default_eta() -> f64 {
+    1.55
+}
+
+fn default_roughness() -> f64 {
+    0.3
+}
+
+fn default_alpha() -> f64 {
+    2.0
+}
+
+/// Logistic distribution of scale `s` restricted to [-pi, pi].
+fn sample_trimmed_logistic(xi: f64, s: f64) -> f64 {
+    let cdf = |x: f64| 1.0 / (1.0 + (-x / s).exp());
+    let k = cdf(PI) - cdf(-PI);
+    (-s * (1.0 / (xi * k + cdf(-PI)) - 1.0).ln()).clamp(-PI, PI)
+}
+
+impl Hair {
+    fn sigma_a(&self) -> DVec3 {
+        let b = self.beta_n;
+        let denominator = 5.969 - 0.215 * b + 2.532 * b.powi(2) - 10.73 * b.powi(3) + 5.574 * b.powi(4) + 0.245 * b.powi(5);
+        self.color.map(|c| (c.clamp(1e-4, 1.0).ln() / denominator).powi(2))
+    }
+}
+
+#[typetag::serde]
+impl Material for Hair {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Vec<(DVec3, Ray)> {
+        let mut rng = rand::thread_rng();
+        let wo = -ray.direction.normalize();
+        let tangent = hit.tangent.unwrap_or_else(|| orthonormal_basis(&hit.normal).0);
+        // offset across the fiber, as curves give it
+        let h = match (hit.tangent, hit.uv) {
+            (Some(_), Some(uv)) => (2.0 * uv.y - 1.0).clamp(-1.0, 1.0),
+            _ => 0.0,
+        };
+        let towards = (wo - tangent * wo.dot(&tangent)).try_normalize(1e-12).unwrap_or_else(|| orthonormal_basis(&tangent).0);
+        let across = tangent.cross(&towards);
+
+        let sin_theta_o = wo.dot(&tangent).clamp(-1.0, 1.0);
+        let cos_theta_o = (1.0 - sin_theta_o * sin_theta_o).max(0.0).sqrt();
+        let sin_theta_t = sin_theta_o / self.eta;
+        let cos_theta_t = (1.0 - sin_theta_t * sin_theta_t).max(1e-8).sqrt();
+        let eta_p = (self.eta * self.eta - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o.max(1e-8);
+        let sin_gamma_t = (h / eta_p).clamp(-1.0, 1.0);
+        let cos_gamma_t = (1.0 - sin_gamma_t * sin_gamma_t).sqrt();
+        let (gamma_o, gamma_t) = (h.asin(), sin_gamma_t.asin());
+
+        // attenuations of R, TT, TRT and all longer paths
+        let transmittance = (-self.sigma_a() * (2.0 * cos_gamma_t / cos_theta_t)).map(f64::exp);
+        let f = fresnel_dielectric(cos_theta_o * (1.0 - h * h).sqrt(), self.eta);
+        let tt = (1.0 - f).powi(2) * transmittance;
+        let trt = tt.component_mul(&transmittance) * f;
+        let rest = (trt.component_mul(&transmittance) * f)
+            .component_div(&(DVec3::repeat(1.0) - transmittance * f).map(|x| x.max(1e-6)));
+        let attenuations = [DVec3::repeat(f), tt, trt, rest];
+
+        let weights = attenuations.map(|a| a.mean());
+        let total = weights.iter().sum::<f64>();
+        if total <= 0.0 {
+            return vec![];
+        }
+        let mut pick = rng.gen::<f64>() * total;
+        let p = (0..3).find(|&p| {
+            pick -= weights[p];
+            pick < 0.0
+        }).unwrap_or(3);
+
+        // longitudinal scattering around the mirror direction, shifted by the scale tilt
+        let beta_m = self.beta_m;
+        let v0 = (0.726 * beta_m + 0.812 * beta_m.powi(2) + 3.7 * beta_m.powi(20)).powi(2).max(1e-4);
+        let variance = [v0, v0 / 4.0, 4.0 * v0, 4.0 * v0][p];
+        let tilt = [-2.0, 1.0, 4.0, 0.0][p] * self.alpha.to_radians();
+        let sin_theta_p = sin_theta_o * tilt.cos() + cos_theta_o * tilt.sin();
+        let cos_theta_p = (cos_theta_o * tilt.cos() - sin_theta_o * tilt.sin()).abs();
+        let xi = rng.gen::<f64>().max(1e-5);
+        let cos_theta = 1.0 + variance * (xi + (1.0 - xi) * (-2.0 / variance).exp()).ln();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let sin_theta_i = (-cos_theta * sin_theta_p + sin_theta * (2.0 * PI * rng.gen::<f64>()).cos() * cos_theta_p).clamp(-1.0, 1.0);
+        let cos_theta_i = (1.0 - sin_theta_i * sin_theta_i).sqrt();
+
+        // azimuthal scattering by the refractions on the way through the fiber
+        let phi = if p < 3 {
+            let beta_n = self.beta_n;
+            let s = (PI / 8.0).sqrt() * (0.265 * beta_n + 1.194 * beta_n.powi(2) + 5.372 * beta_n.powi(22));
+            let p = p as f64;
+            2.0 * p * gamma_t - 2.0 * gamma_o + p * PI + sample_trimmed_logistic(rng.gen(), s)
+        } else {
+            2.0 * PI * rng.gen::<f64>()
+        };
+
+        let wi = sin_theta_i * tangent + cos_theta_i * (phi.cos() * towards + phi.sin() * across);
+        vec![(attenuations[p] * (total / weights[p]), Ray::new(hit.point, wi))]
+    }
+
+    fn emit(&self, _ray: &Ray, _hit: &HitRecord) -> DVec3 {
+        DVec3::zeros()
+    }
+}
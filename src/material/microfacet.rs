@@ -0,0 +1,111 @@
+use std::f64::consts::PI;
+
+use nalgebra_glm::DVec3;
+
+use crate::utils::orthonormal_basis;
+
+/// Trowbridge-Reitz (GGX) distribution of microfacet normals, isotropic with width `alpha`.
+/// Directions are in a local frame with the macro normal along z.
+pub(crate) struct Ggx {
+    pub alpha: f64,
+}
+
+impl Ggx {
+    /// Artist friendly roughness in [0, 1] is squared into the width, kept off zero
+    /// so that smooth surfaces still have a well-defined distribution.
+    pub fn from_roughness(roughness: f64) -> Self {
+        Self { alpha: (roughness * roughness).clamp(1e-4, 1.0) }
+    }
+
+    /// Smith's auxiliary function for the masking of direction `w`.
+    fn lambda(&self, w: &DVec3) -> f64 {
+        let cos2 = w.z * w.z;
+        if cos2 <= 0.0 {
+            return f64::INFINITY;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0) / 2.0
+    }
+
+    pub fn g1(&self, w: &DVec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Height correlated masking and shadowing of the pair of directions.
+    pub fn g2(&self, wo: &DVec3, wi: &DVec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Microfacet normal visible from `wo`, sampled by its projected area (Heitz 2018),
+    /// from two uniform numbers in [0, 1). `wo` must lie above the surface.
+    pub fn sample_visible_normal(&self, wo: &DVec3, u1: f64, u2: f64) -> DVec3 {
+        let vh = DVec3::new(self.alpha * wo.x, self.alpha * wo.y, wo.z).normalize();
+        let length2 = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if length2 > 0.0 { DVec3::new(-vh.y, vh.x, 0.0) / length2.sqrt() } else { DVec3::x() };
+        let t2 = vh.cross(&t1);
+        let (r, phi) = (u1.sqrt(), 2.0 * PI * u2);
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+        DVec3::new(self.alpha * nh.x, self.alpha * nh.y, nh.z.max(1e-9)).normalize()
+    }
+}
+
+/// Local frame around `normal`: maps world directions to ones with the normal along z, and back.
+pub(crate) struct Frame {
+    tangent: DVec3,
+    bitangent: DVec3,
+    normal: DVec3,
+}
+
+impl Frame {
+    pub fn new(normal: &DVec3) -> Self {
+        let normal = normal.normalize();
+        let (tangent, bitangent) = orthonormal_basis(&normal);
+        Self { tangent, bitangent, normal }
+    }
+
+    pub fn to_local(&self, v: &DVec3) -> DVec3 {
+        DVec3::new(v.dot(&self.tangent), v.dot(&self.bitangent), v.dot(&self.normal))
+    }
+
+    pub fn to_world(&self, v: &DVec3) -> DVec3 {
+        v.x * self.tangent + v.y * self.bitangent + v.z * self.normal
+    }
+}
+
+/// Fraction of light reflected off a dielectric at incidence `cos_i`, unpolarized.
+/// `eta` is the ratio of the indices of refraction inside over outside,
+/// and negative `cos_i` means the light arrives from the inside.
+pub(crate) fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let (cos_i, eta) = if cos_i < 0.0 { (-cos_i, 1.0 / eta) } else { (cos_i, eta) };
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (parallel * parallel + perpendicular * perpendicular) / 2.0
+}
+
+/// Fraction of light reflected off a conductor with complex index of refraction `eta + i k`,
+/// unpolarized, per color channel.
+pub(crate) fn fresnel_conductor(cos_i: f64, eta: &DVec3, k: &DVec3) -> DVec3 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let (cos2, sin2) = (cos_i * cos_i, 1.0 - cos_i * cos_i);
+    DVec3::from_fn(|c, _| {
+        let (eta2, k2) = (eta[c] * eta[c], k[c] * k[c]);
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos_i * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        (rs + rp) / 2.0
+    })
+}
@@ -2,19 +2,26 @@ use nalgebra_glm as glm;
 
 
 use crate::hit::{Ray, HitRecord};
-use typetag;
 
 mod diffuse;
 mod light;
 mod wood;
 mod metal;
 mod dielectric;
+mod conductor;
+mod rough_dielectric;
+mod microfacet;
+mod principled;
+mod hair;
+mod texture;
 
 pub use diffuse::*;
 pub use light::*;
 pub use wood::*;
 pub use metal::*;
 pub use dielectric::*;
+pub use principled::*;
+pub use texture::*;
 
 #[typetag::serde(tag = "type")]
 pub trait Material: Send + Sync {
@@ -0,0 +1,164 @@
+use std::f64::consts::PI;
+
+use nalgebra_glm::DVec3;
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+
+use crate::hit::{HitRecord, Ray};
+
+use super::{ImageTexture, Material, textured_color, microfacet::{Frame, Ggx}, rough_dielectric::sample_rough_dielectric};
+
+/// The principled BSDF of Burley 2012, as in glTF and most DCC tools. All parameters but the
+/// base color and the index of refraction range from 0 to 1. Each scattering picks one of
+/// the diffuse, specular, clearcoat and transmission lobes and samples its direction.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Principled {
+    pub base_color: DVec3,
+    /// Multiplies `base_color` on shapes with UVs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub texture: Option<ImageTexture>,
+    #[serde(default)]
+    pub metallic: f64,
+    #[serde(default = "default_half")]
+    pub roughness: f64,
+    /// Reflectance of dielectrics at normal incidence, 0.5 being the 4% of common materials.
+    #[serde(default = "default_half")]
+    pub specular: f64,
+    /// Tints the specular reflection of dielectrics towards the base color.
+    #[serde(default)]
+    pub specular_tint: f64,
+    /// Extra reflection at grazing angles, for cloth.
+    #[serde(default)]
+    pub sheen: f64,
+    /// A second, colorless specular layer on top, for lacquer and car paint.
+    #[serde(default)]
+    pub clearcoat: f64,
+    #[serde(default = "default_clearcoat_gloss")]
+    pub clearcoat_gloss: f64,
+    /// Fraction of the dielectric that is glass instead of diffuse, tinted by the base color.
+    #[serde(default)]
+    pub transmission: f64,
+    /// Index of refraction of the transmission.
+    #[serde(default = "default_ior")]
+    pub ior: f64,
+}
+
+fn default_half() -> f64 {
+    0.5
+}
+
+fn default_clearcoat_gloss() -> f64 {
+    1.0
+}
+
+fn default_ior() -> f64 {
+    1.5
+}
+
+fn schlick_weight(cos: f64) -> f64 {
+    (1.0 - cos.clamp(0.0, 1.0)).powi(5)
+}
+
+fn luminance(color: &DVec3) -> f64 {
+    color.dot(&DVec3::new(0.2126, 0.7152, 0.0722))
+}
+
+impl Principled {
+    pub fn new(base_color: DVec3) -> Self {
+        Self {
+            base_color,
+            texture: None,
+            metallic: 0.0,
+            roughness: default_half(),
+            specular: default_half(),
+            specular_tint: 0.0,
+            sheen: 0.0,
+            clearcoat: 0.0,
+            clearcoat_gloss: default_clearcoat_gloss(),
+            transmission: 0.0,
+            ior: default_ior(),
+        }
+    }
+}
+
+#[typetag::serde]
+impl Material for Principled {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Vec<(DVec3, Ray)> {
+        let base = textured_color(&self.base_color, &self.texture, hit);
+        let (metallic, transmission) = (self.metallic.clamp(0.0, 1.0), self.transmission.clamp(0.0, 1.0));
+        let tint = if luminance(&base) > 0.0 { base / luminance(&base) } else { DVec3::repeat(1.0) };
+        let dielectric_f0 = 0.08 * self.specular * (DVec3::repeat(1.0) + (tint - DVec3::repeat(1.0)) * self.specular_tint);
+        let f0 = dielectric_f0 + (base - dielectric_f0) * metallic;
+
+        // how much of the surface each lobe covers, and how often it is sampled
+        let coverage = [
+            (1.0 - metallic) * (1.0 - transmission),
+            1.0 - (1.0 - metallic) * transmission,
+            (1.0 - metallic) * transmission,
+            0.25 * self.clearcoat.clamp(0.0, 1.0),
+        ];
+        let importance = [coverage[0], coverage[1] * f0.mean().max(0.1), coverage[2], coverage[3] * 0.4];
+        let total = importance.iter().sum::<f64>();
+        if total <= 0.0 {
+            return vec![];
+        }
+        let mut rng = rand::thread_rng();
+        let mut pick = rng.gen::<f64>() * total;
+        let lobe = (0..3).find(|&i| {
+            pick -= importance[i];
+            pick < 0.0
+        }).unwrap_or(3);
+        let scale = coverage[lobe] * total / importance[lobe];
+
+        let wo_world = -ray.direction.normalize();
+        if lobe == 2 {
+            let entering = wo_world.dot(&hit.normal) > 0.0;
+            let (normal, eta) = if entering { (hit.normal, self.ior) } else { (-hit.normal, 1.0 / self.ior) };
+            let frame = Frame::new(&normal);
+            let wo = frame.to_local(&wo_world);
+            let ggx = Ggx::from_roughness(self.roughness);
+            let Some((wi, transmitted, weight)) = (wo.z > 0.0).then(|| sample_rough_dielectric(&wo, eta, &ggx, &mut rng)).flatten() else {
+                return vec![];
+            };
+            // light passes two boundaries on its way through, each tinting by the root of the base color
+            let color = if transmitted { base.map(f64::sqrt) } else { DVec3::repeat(1.0) };
+            return vec![(color * weight * scale, Ray::new(hit.point, frame.to_world(&wi)))];
+        }
+
+        // the opaque lobes reflect off both sides
+        let normal = if wo_world.dot(&hit.normal) < 0.0 { -hit.normal } else { hit.normal };
+        let frame = Frame::new(&normal);
+        let wo = frame.to_local(&wo_world);
+        if wo.z <= 0.0 {
+            return vec![];
+        }
+        let (wi, weight) = if lobe == 0 {
+            // Burley's diffuse with retro-reflection at grazing angles, and sheen
+            let (r, phi) = (rng.gen::<f64>().sqrt(), 2.0 * PI * rng.gen::<f64>());
+            let wi = DVec3::new(r * phi.cos(), r * phi.sin(), (1.0 - r * r).max(0.0).sqrt());
+            let cos_d = wi.dot(&(wi + wo).normalize());
+            let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+            let retro = (1.0 + (fd90 - 1.0) * schlick_weight(wi.z)) * (1.0 + (fd90 - 1.0) * schlick_weight(wo.z));
+            let sheen = self.sheen * PI * schlick_weight(cos_d);
+            (wi, base * retro + DVec3::repeat(sheen))
+        } else {
+            let (ggx, f0) = if lobe == 1 {
+                (Ggx::from_roughness(self.roughness), f0)
+            } else {
+                (Ggx { alpha: 0.1 + (0.001 - 0.1) * self.clearcoat_gloss.clamp(0.0, 1.0) }, DVec3::repeat(0.04))
+            };
+            let m = ggx.sample_visible_normal(&wo, rng.gen(), rng.gen());
+            let wi = 2.0 * wo.dot(&m) * m - wo;
+            if wi.z <= 0.0 {
+                return vec![];
+            }
+            let fresnel = f0 + (DVec3::repeat(1.0) - f0) * schlick_weight(wo.dot(&m));
+            (wi, fresnel * (ggx.g2(&wo, &wi) / ggx.g1(&wo)))
+        };
+        vec![(weight * scale, Ray::new(hit.point, frame.to_world(&wi)))]
+    }
+
+    fn emit(&self, _ray: &Ray, _hit: &HitRecord) -> DVec3 {
+        DVec3::zeros()
+    }
+}
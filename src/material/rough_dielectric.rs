@@ -0,0 +1,61 @@
+use nalgebra_glm::DVec3;
+use rand::{Rng, rngs::ThreadRng};
+use serde::{Serialize, Deserialize};
+
+use crate::hit::{HitRecord, Ray};
+
+use super::{Material, microfacet::{Frame, Ggx, fresnel_dielectric}};
+
+/// Frosted glass: GGX microfacets that reflect or transmit by their Fresnel reflectance (Walter et al. 2007).
+/// The normal points to the outside, where the index of refraction is 1.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RoughDielectric {
+    /// index of refraction
+    pub eta: f64,
+    /// From 0 (smooth) to 1.
+    #[serde(default)]
+    pub roughness: f64,
+}
+
+/// Direction scattered by a rough dielectric boundary from `wo`, in a local frame on the side of `wo`,
+/// with `eta` the index of refraction beyond the boundary over that on the side of `wo`.
+/// Returns the direction, whether it is transmitted, and its weight.
+pub(crate) fn sample_rough_dielectric(wo: &DVec3, eta: f64, ggx: &Ggx, rng: &mut ThreadRng) -> Option<(DVec3, bool, f64)> {
+    let m = ggx.sample_visible_normal(wo, rng.gen(), rng.gen());
+    let cos_o = wo.dot(&m);
+    // Reflecting with the probability of the Fresnel reflectance cancels it from the weight.
+    let (wi, transmitted) = if rng.gen::<f64>() < fresnel_dielectric(cos_o, eta) {
+        (2.0 * cos_o * m - wo, false)
+    } else {
+        let sin2_t = (1.0 - cos_o * cos_o) / (eta * eta);
+        let cos_t = (1.0 - sin2_t).max(0.0).sqrt();
+        (-wo / eta + (cos_o / eta - cos_t) * m, true)
+    };
+    if (wi.z < 0.0) != transmitted || wi.z == 0.0 {
+        return None;
+    }
+    Some((wi, transmitted, ggx.g2(wo, &wi) / ggx.g1(wo)))
+}
+
+#[typetag::serde]
+impl Material for RoughDielectric {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Vec<(DVec3, Ray)> {
+        let wo_world = -ray.direction.normalize();
+        let entering = wo_world.dot(&hit.normal) > 0.0;
+        let (normal, eta) = if entering { (hit.normal, self.eta) } else { (-hit.normal, 1.0 / self.eta) };
+        let frame = Frame::new(&normal);
+        let wo = frame.to_local(&wo_world);
+        if wo.z <= 0.0 {
+            return vec![];
+        }
+        let ggx = Ggx::from_roughness(self.roughness);
+        match sample_rough_dielectric(&wo, eta, &ggx, &mut rand::thread_rng()) {
+            Some((wi, _, weight)) => vec![(DVec3::repeat(weight), Ray::new(hit.point, frame.to_world(&wi)))],
+            None => vec![],
+        }
+    }
+
+    fn emit(&self, _ray: &Ray, _hit: &HitRecord) -> DVec3 {
+        DVec3::zeros()
+    }
+}
@@ -0,0 +1,112 @@
+use std::{path::PathBuf, sync::Arc};
+
+use cached::proc_macro::cached;
+use nalgebra_glm::{DVec2, DVec3};
+use serde::{Serialize, Deserialize};
+
+use crate::{hit::HitRecord, utils::scene_path};
+
+/// An image file mapped onto the UVs of the shape.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "PathBuf", into = "PathBuf")]
+pub struct ImageTexture {
+    path: PathBuf,
+    image: Arc<image::Rgb32FImage>,
+}
+
+/// Images are shared by all textures with the same path.
+#[cached(result = true)]
+fn load_image(path: PathBuf) -> Result<Arc<image::Rgb32FImage>, String> {
+    let image = image::open(&path).map_err(|e| format!("failed to load texture {}: {}", path.display(), e))?;
+    Ok(Arc::new(image.into_rgb32f()))
+}
+
+impl ImageTexture {
+    pub fn load(path: PathBuf) -> anyhow::Result<Self> {
+        let image = load_image(path.clone()).map_err(anyhow::Error::msg)?;
+        Ok(Self { path, image })
+    }
+
+    /// Texture of an image that does not come from a file of its own, such as one embedded in a model.
+    /// `path` only names it when the texture is serialized.
+    pub fn from_image(path: PathBuf, image: image::DynamicImage) -> Self {
+        Self { path, image: Arc::new(image.into_rgb32f()) }
+    }
+
+    /// Color at `uv`, repeating the image outside [0, 1]. v points up the image.
+    pub fn sample(&self, uv: &DVec2) -> DVec3 {
+        let u = uv.x - uv.x.floor();
+        let v = 1.0 - (uv.y - uv.y.floor());
+
+        let iw = ((u * self.image.width() as f64) as u32).min(self.image.width() - 1);
+        let ih = ((v * self.image.height() as f64) as u32).min(self.image.height() - 1);
+
+        self.pixel(iw, ih)
+    }
+
+    /// Like [`Self::sample`], but blends the four nearest pixels, for values that should
+    /// vary smoothly across pixels such as heights.
+    pub fn sample_bilinear(&self, uv: &DVec2) -> DVec3 {
+        let (width, height) = (self.image.width(), self.image.height());
+        let x = (uv.x - uv.x.floor()) * width as f64 - 0.5;
+        let y = (1.0 - (uv.y - uv.y.floor())) * height as f64 - 0.5;
+        let (fx, fy) = (x - x.floor(), y - y.floor());
+        let wrap = |i: f64, n: u32| (i as i64).rem_euclid(n as i64) as u32;
+        let (x0, y0) = (wrap(x.floor(), width), wrap(y.floor(), height));
+        let (x1, y1) = ((x0 + 1) % width, (y0 + 1) % height);
+
+        let top = self.pixel(x0, y0) * (1.0 - fx) + self.pixel(x1, y0) * fx;
+        let bottom = self.pixel(x0, y1) * (1.0 - fx) + self.pixel(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    fn pixel(&self, x: u32, y: u32) -> DVec3 {
+        let pixel = self.image.get_pixel(x, y).0;
+        DVec3::new(pixel[0] as f64, pixel[1] as f64, pixel[2] as f64)
+    }
+}
+
+impl TryFrom<PathBuf> for ImageTexture {
+    type Error = anyhow::Error;
+
+    /// Paths in scene files are relative to the scene file.
+    fn try_from(path: PathBuf) -> anyhow::Result<Self> {
+        let texture = Self::load(scene_path(&path))?;
+        Ok(Self { path, ..texture })
+    }
+}
+
+impl From<ImageTexture> for PathBuf {
+    fn from(texture: ImageTexture) -> Self {
+        texture.path
+    }
+}
+
+/// `color` at the hit, multiplied by `texture` at its UV and by its vertex color where the shape has them.
+pub(crate) fn textured_color(color: &DVec3, texture: &Option<ImageTexture>, hit: &HitRecord) -> DVec3 {
+    let color = match (texture, &hit.uv) {
+        (Some(texture), Some(uv)) => color.component_mul(&texture.sample(uv)),
+        _ => *color,
+    };
+    match hit.color {
+        Some(vertex_color) => color.component_mul(&vertex_color),
+        None => color,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bilinear_sample_blends_neighboring_pixels() {
+        let image = image::RgbImage::from_fn(2, 1, |x, _| image::Rgb([if x == 0 { 0 } else { 255 }; 3]));
+        let texture = ImageTexture::from_image(PathBuf::from("ramp.png"), image::DynamicImage::ImageRgb8(image));
+        let sample = |u| texture.sample_bilinear(&DVec2::new(u, 0.5)).x;
+        assert!((sample(0.25) - 0.0).abs() < 1e-6);
+        assert!((sample(0.5) - 0.5).abs() < 1e-6);
+        assert!((sample(0.75) - 1.0).abs() < 1e-6);
+        // halfway past the last pixel center wraps around to the first one
+        assert!((sample(1.0) - 0.5).abs() < 1e-6);
+    }
+}
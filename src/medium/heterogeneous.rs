@@ -0,0 +1,216 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::ensure;
+use nalgebra_glm::DVec3;
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+
+use crate::{hit::Ray, utils::scene_path};
+use super::Medium;
+
+/// Medium whose density varies through space. The coefficients are those at density 1.
+/// Scattering is sampled by delta tracking against the largest density.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Heterogeneous {
+    pub sigma_a: f64,
+    pub sigma_s: f64,
+    #[serde(default)]
+    pub g: f64,
+    pub density: Density,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Density {
+    Grid(VoxelGrid),
+    Noise(NoiseDensity),
+}
+
+impl Density {
+    fn at(&self, point: &DVec3) -> f64 {
+        match self {
+            Density::Grid(grid) => grid.at(point),
+            Density::Noise(noise) => noise.at(point),
+        }
+    }
+
+    fn max(&self) -> f64 {
+        match self {
+            Density::Grid(grid) => grid.max,
+            Density::Noise(_) => 1.0,
+        }
+    }
+}
+
+#[typetag::serde]
+impl Medium for Heterogeneous {
+    fn sample_scatter(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, DVec3)> {
+        let sigma_t = self.sigma_a + self.sigma_s;
+        let majorant = sigma_t * self.density.max();
+        if majorant <= 0.0 {
+            return None;
+        }
+        let mut rng = rand::thread_rng();
+        let mut toi = t_min;
+        loop {
+            toi -= (1.0 - rng.gen::<f64>()).ln() / majorant / ray.direction.norm();
+            if toi >= t_max {
+                return None;
+            }
+            // a real collision rather than a null one
+            let point = ray.origin + toi * ray.direction;
+            if rng.gen::<f64>() * self.density.max() < self.density.at(&point) {
+                return Some((toi, DVec3::repeat(self.sigma_s / sigma_t)));
+            }
+        }
+    }
+
+    fn asymmetry(&self) -> f64 {
+        self.g
+    }
+}
+
+/// Densities on a regular grid spanning the box from `min` to `max`, zero outside of it.
+/// The file holds `resolution[0] * resolution[1] * resolution[2]` little-endian f32 values,
+/// x varying fastest, then y, then z. Its path is relative to the scene file.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "VoxelGridFile", into = "VoxelGridFile")]
+pub struct VoxelGrid {
+    file: VoxelGridFile,
+    values: Vec<f32>,
+    max: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VoxelGridFile {
+    pub path: PathBuf,
+    pub resolution: [usize; 3],
+    pub min: DVec3,
+    pub max: DVec3,
+}
+
+impl TryFrom<VoxelGridFile> for VoxelGrid {
+    type Error = anyhow::Error;
+
+    fn try_from(file: VoxelGridFile) -> anyhow::Result<Self> {
+        let bytes = fs::read(scene_path(&file.path))
+            .map_err(|e| anyhow::anyhow!("failed to read voxel grid {}: {}", file.path.display(), e))?;
+        let [nx, ny, nz] = file.resolution;
+        ensure!(nx > 0 && ny > 0 && nz > 0, "voxel grid {} is empty", file.path.display());
+        let count = nx.checked_mul(ny).and_then(|n| n.checked_mul(nz))
+            .filter(|n| n.checked_mul(4).is_some())
+            .ok_or_else(|| anyhow::anyhow!("voxel grid {} resolution {:?} is too large", file.path.display(), file.resolution))?;
+        ensure!(bytes.len() == 4 * count, "voxel grid {} should hold {} values", file.path.display(), count);
+        let values = bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]).max(0.0)).collect::<Vec<_>>();
+        let max = values.iter().fold(0.0f32, |m, &v| m.max(v)) as f64;
+        Ok(Self { file, values, max })
+    }
+}
+
+impl From<VoxelGrid> for VoxelGridFile {
+    fn from(grid: VoxelGrid) -> Self {
+        grid.file
+    }
+}
+
+impl VoxelGrid {
+    fn value(&self, x: usize, y: usize, z: usize) -> f64 {
+        let [nx, ny, _] = self.file.resolution;
+        self.values[x + nx * (y + ny * z)] as f64
+    }
+
+    /// Trilinear interpolation between the voxel centers.
+    fn at(&self, point: &DVec3) -> f64 {
+        let relative = (point - self.file.min).component_div(&(self.file.max - self.file.min));
+        if relative.iter().any(|&r| !(0.0..=1.0).contains(&r)) {
+            return 0.0;
+        }
+        let mut corner = [0; 3];
+        let mut weight = [0.0; 3];
+        for axis in 0..3 {
+            let n = self.file.resolution[axis];
+            let x = (relative[axis] * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+            corner[axis] = (x.floor() as usize).min(n.saturating_sub(2));
+            weight[axis] = x - corner[axis] as f64;
+        }
+        let mut density = 0.0;
+        for i in 0..8 {
+            let offset = [i & 1, (i >> 1) & 1, (i >> 2) & 1];
+            let mut w = 1.0;
+            let mut index = [0; 3];
+            for axis in 0..3 {
+                let n = self.file.resolution[axis];
+                index[axis] = (corner[axis] + offset[axis]).min(n - 1);
+                w *= if offset[axis] == 1 { weight[axis] } else { 1.0 - weight[axis] };
+            }
+            density += w * self.value(index[0], index[1], index[2]);
+        }
+        density
+    }
+}
+
+/// Fractal value noise in [0, 1], for billowing smoke without a simulation.
+/// Densities below `threshold` are cut away and the rest rescaled to [0, 1].
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NoiseDensity {
+    /// Features per unit of distance of the first octave.
+    #[serde(default = "default_frequency")]
+    pub frequency: f64,
+    #[serde(default = "default_octaves")]
+    pub octaves: u32,
+    #[serde(default)]
+    pub threshold: f64,
+    #[serde(default)]
+    pub seed: u32,
+}
+
+fn default_frequency() -> f64 {
+    1.0
+}
+
+fn default_octaves() -> u32 {
+    4
+}
+
+impl NoiseDensity {
+    /// Pseudo-random value in [0, 1] at a lattice point.
+    fn lattice(&self, x: i64, y: i64, z: i64) -> f64 {
+        let mut h = (x as u64).wrapping_mul(0x9E3779B97F4A7C15)
+            ^ (y as u64).wrapping_mul(0xC2B2AE3D27D4EB4F)
+            ^ (z as u64).wrapping_mul(0x165667B19E3779F9)
+            ^ (self.seed as u64).wrapping_mul(0x27D4EB2F165667C5);
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xFF51AFD7ED558CCD);
+        h ^= h >> 33;
+        (h >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn value_noise(&self, p: &DVec3) -> f64 {
+        let base = p.map(f64::floor);
+        let f = p - base;
+        let smooth = f.map(|t| t * t * (3.0 - 2.0 * t));
+        let (x, y, z) = (base.x as i64, base.y as i64, base.z as i64);
+        let mut value = 0.0;
+        for i in 0..8 {
+            let (dx, dy, dz) = (i & 1, (i >> 1) & 1, (i >> 2) & 1);
+            let w = (if dx == 1 { smooth.x } else { 1.0 - smooth.x })
+                * (if dy == 1 { smooth.y } else { 1.0 - smooth.y })
+                * (if dz == 1 { smooth.z } else { 1.0 - smooth.z });
+            value += w * self.lattice(x + dx, y + dy, z + dz);
+        }
+        value
+    }
+
+    fn at(&self, point: &DVec3) -> f64 {
+        let (mut sum, mut amplitude, mut total) = (0.0, 1.0, 0.0);
+        let mut p = point * self.frequency;
+        for _ in 0..self.octaves.max(1) {
+            sum += amplitude * self.value_noise(&p);
+            total += amplitude;
+            amplitude *= 0.5;
+            p *= 2.0;
+        }
+        let noise = sum / total;
+        ((noise - self.threshold) / (1.0 - self.threshold).max(1e-6)).clamp(0.0, 1.0)
+    }
+}
@@ -0,0 +1,37 @@
+use nalgebra_glm::DVec3;
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+
+use crate::hit::Ray;
+use super::Medium;
+
+/// Medium of constant density, such as fog or thin smoke.
+/// Coefficients are per unit of distance.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Homogeneous {
+    pub sigma_a: f64,
+    pub sigma_s: f64,
+    #[serde(default)]
+    pub g: f64,
+}
+
+#[typetag::serde]
+impl Medium for Homogeneous {
+    fn sample_scatter(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, DVec3)> {
+        let sigma_t = self.sigma_a + self.sigma_s;
+        if sigma_t <= 0.0 {
+            return None;
+        }
+        // free-flight distance, with the ray direction being a unit vector
+        let distance = -(1.0 - rand::thread_rng().gen::<f64>()).ln() / sigma_t;
+        let toi = t_min + distance / ray.direction.norm();
+        if toi >= t_max {
+            return None;
+        }
+        Some((toi, DVec3::repeat(self.sigma_s / sigma_t)))
+    }
+
+    fn asymmetry(&self) -> f64 {
+        self.g
+    }
+}
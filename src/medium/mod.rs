@@ -0,0 +1,73 @@
+mod heterogeneous;
+mod homogeneous;
+
+use std::sync::Arc;
+
+use nalgebra_glm::DVec3;
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+
+use crate::{hit::Ray, shape::Shape, utils::orthonormal_basis};
+
+/// A participating medium that light scatters in on its way between surfaces.
+#[typetag::serde(tag = "type")]
+pub trait Medium: Send + Sync {
+    /// Sample where between the tois `t_min` and `t_max` the ray first scatters in the medium.
+    /// Returns the toi and the fraction of light that is scattered rather than absorbed there,
+    /// or None if the ray passes through.
+    fn sample_scatter(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, DVec3)>;
+
+    /// Asymmetry of the Henyey-Greenstein phase function, from -1 (back) to 1 (forward scattering).
+    fn asymmetry(&self) -> f64;
+}
+
+/// A medium filling the inside of a closed shape, see `Shape::is_closed`.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "VolumeData")]
+pub struct Volume {
+    pub boundary: Arc<dyn Shape>,
+    pub medium: Arc<dyn Medium>,
+}
+
+#[derive(Deserialize)]
+struct VolumeData {
+    boundary: Arc<dyn Shape>,
+    medium: Arc<dyn Medium>,
+}
+
+impl TryFrom<VolumeData> for Volume {
+    type Error = anyhow::Error;
+
+    fn try_from(data: VolumeData) -> anyhow::Result<Self> {
+        anyhow::ensure!(data.boundary.is_closed(),
+            "volume boundaries must be closed shapes such as spheres, cuboids, tori, capped cylinders and cones, or CSG shapes");
+        Ok(Volume { boundary: data.boundary, medium: data.medium })
+    }
+}
+
+impl Volume {
+    /// Like [`Medium::sample_scatter`], restricted to the parts of the ray inside the boundary.
+    pub fn sample_scatter(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, DVec3)> {
+        self.boundary.intervals(ray)?
+            .into_iter()
+            .map(|(entry, exit)| (entry.toi.max(t_min), exit.toi.min(t_max)))
+            .filter(|(start, end)| start < end)
+            .find_map(|(start, end)| self.medium.sample_scatter(ray, start, end))
+    }
+}
+
+/// Direction of light scattered from `direction` by the Henyey-Greenstein phase function with asymmetry `g`.
+pub fn sample_henyey_greenstein(direction: &DVec3, g: f64) -> DVec3 {
+    let mut rng = rand::thread_rng();
+    let (xi, phi) = (rng.gen::<f64>(), 2.0 * std::f64::consts::PI * rng.gen::<f64>());
+    let cos_theta = if g.abs() < 1e-3 {
+        1.0 - 2.0 * xi
+    } else {
+        let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+        (1.0 + g * g - s * s) / (2.0 * g)
+    };
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let forward = direction.normalize();
+    let (t, b) = orthonormal_basis(&forward);
+    cos_theta * forward + sin_theta * (phi.cos() * t + phi.sin() * b)
+}
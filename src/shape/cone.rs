@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use bvh::aabb::Bounded;
+use nalgebra_glm::DVec3;
+use serde::{Deserialize, Serialize};
+
+use crate::{hit::{HitRecord, Ray}, material::Material, utils::solve_quadratic};
+use super::{Shape, aabb_from, disk_aabb, disk_toi, nearest_hit, pair_up, cylinder::default_capped};
+
+/// Cone, or truncated cone, around the segment from `p0` to `p1`.
+/// Its radius goes linearly from `r0` at `p0` to `r1` at `p1`; set `r1` to zero for a pointed cone.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Cone {
+    pub p0: DVec3,
+    pub p1: DVec3,
+    pub r0: f64,
+    pub r1: f64,
+    /// Close the ends with disks.
+    #[serde(default = "default_capped")]
+    pub capped: bool,
+    pub material: Arc<dyn Material>,
+}
+
+impl Cone {
+    /// Every intersection of the ray with the surface, including those behind the origin.
+    pub(super) fn all_hits(&self, ray: &Ray) -> Vec<HitRecord> {
+        let axis = self.p1 - self.p0;
+        let height = axis.norm();
+        let w = axis / height;
+        // radius at height y is r0 + k * y
+        let k = (self.r1 - self.r0) / height;
+
+        let oc = ray.origin - self.p0;
+        let (yo, yd) = (oc.dot(&w), ray.direction.dot(&w));
+        let o_perp = oc - yo * w;
+        let d_perp = ray.direction - yd * w;
+        let ro = self.r0 + k * yo;
+
+        let mut hits = solve_quadratic([
+            o_perp.norm_squared() - ro * ro,
+            2.0 * (o_perp.dot(&d_perp) - k * yd * ro),
+            d_perp.norm_squared() - k * k * yd * yd,
+        ])
+        .into_iter()
+        .filter_map(|toi| {
+            let point = ray.origin + toi * ray.direction;
+            let y = (point - self.p0).dot(&w);
+            if !(0.0..=height).contains(&y) {
+                return None;
+            }
+            let radial = point - self.p0 - y * w;
+            // gradient of |radial| - (r0 + k * y)
+            let normal = (radial.normalize() - k * w).normalize();
+            Some(HitRecord::new(toi, point, normal))
+        })
+        .collect::<Vec<_>>();
+
+        if self.capped {
+            for (center, normal, radius) in [(self.p0, -w, self.r0), (self.p1, w, self.r1)] {
+                if radius <= 0.0 {
+                    continue;
+                }
+                if let Some(toi) = disk_toi(ray, &center, &normal, radius) {
+                    hits.push(HitRecord::new(toi, ray.origin + toi * ray.direction, normal));
+                }
+            }
+        }
+        hits.sort_by(|a, b| a.toi.partial_cmp(&b.toi).unwrap());
+        hits
+    }
+}
+
+impl Bounded for Cone {
+    fn aabb(&self) -> bvh::aabb::AABB {
+        let w = self.p1 - self.p0;
+        let (min0, max0) = disk_aabb(&self.p0, &w, self.r0);
+        let (min1, max1) = disk_aabb(&self.p1, &w, self.r1);
+        aabb_from(&min0, &max0).join(&aabb_from(&min1, &max1))
+    }
+}
+
+#[typetag::serde]
+impl Shape for Cone {
+    fn hit(&self, ray: &Ray) -> Option<HitRecord> {
+        nearest_hit(self.all_hits(ray))
+    }
+
+    fn material(&self, _hit: &HitRecord) -> Arc<dyn Material> {
+        self.material.clone()
+    }
+
+    fn intervals(&self, ray: &Ray) -> Option<Vec<(HitRecord, HitRecord)>> {
+        if !self.capped {
+            return None;
+        }
+        Some(pair_up(self.all_hits(ray)))
+    }
+
+    fn is_closed(&self) -> bool {
+        self.capped
+    }
+}
@@ -0,0 +1,187 @@
+use std::sync::Arc;
+
+use bvh::aabb::Bounded;
+use serde::{Deserialize, Serialize};
+
+use crate::{hit::{HitRecord, Ray}, material::Material};
+use super::Shape;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    /// `left` with `right` cut out of it.
+    Difference,
+}
+
+impl CsgOperation {
+    fn contains(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+/// Boolean combination of two closed shapes, see `Shape::is_closed`.
+/// Each surface keeps the material of the child it comes from, unless `material` is set.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "CsgData")]
+pub struct Csg {
+    pub operation: CsgOperation,
+    pub left: Arc<dyn Shape>,
+    pub right: Arc<dyn Shape>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub material: Option<Arc<dyn Material>>,
+}
+
+#[derive(Deserialize)]
+struct CsgData {
+    operation: CsgOperation,
+    left: Arc<dyn Shape>,
+    right: Arc<dyn Shape>,
+    #[serde(default)]
+    material: Option<Arc<dyn Material>>,
+}
+
+impl TryFrom<CsgData> for Csg {
+    type Error = anyhow::Error;
+
+    fn try_from(data: CsgData) -> anyhow::Result<Self> {
+        anyhow::ensure!(data.left.is_closed() && data.right.is_closed(),
+            "CSG children must be closed shapes such as spheres, cuboids, tori, capped cylinders and cones, or other CSG shapes");
+        Ok(Csg { operation: data.operation, left: data.left, right: data.right, material: data.material })
+    }
+}
+
+impl Csg {
+    /// Boundary crossings of the child, tagged with the material of the surface hit.
+    fn child_events(&self, child: &Arc<dyn Shape>, is_left: bool, ray: &Ray) -> Vec<(HitRecord, bool)> {
+        let mut events = vec![];
+        for (entry, exit) in child.intervals(ray).unwrap_or_default() {
+            for mut hit in [entry, exit] {
+                if hit.material.is_none() {
+                    hit.material = Some(child.material(&hit));
+                }
+                // surfaces of the cut-out face into it
+                if !is_left && matches!(self.operation, CsgOperation::Difference) {
+                    hit.normal = -hit.normal;
+                }
+                events.push((hit, is_left));
+            }
+        }
+        events
+    }
+
+    /// All boundary hits of the combined shape, sorted by toi.
+    fn boundary(&self, ray: &Ray) -> Vec<HitRecord> {
+        let mut events = self.child_events(&self.left, true, ray);
+        events.extend(self.child_events(&self.right, false, ray));
+        events.sort_by(|a, b| a.0.toi.partial_cmp(&b.0.toi).unwrap());
+
+        let (mut in_left, mut in_right) = (false, false);
+        let mut boundary = vec![];
+        for (mut hit, is_left) in events {
+            let was_inside = self.operation.contains(in_left, in_right);
+            if is_left {
+                in_left = !in_left;
+            } else {
+                in_right = !in_right;
+            }
+            if was_inside != self.operation.contains(in_left, in_right) {
+                if self.material.is_some() {
+                    hit.material = self.material.clone();
+                }
+                boundary.push(hit);
+            }
+        }
+        boundary
+    }
+}
+
+impl Bounded for Csg {
+    fn aabb(&self) -> bvh::aabb::AABB {
+        match self.operation {
+            CsgOperation::Union => self.left.aabb().join(&self.right.aabb()),
+            CsgOperation::Intersection | CsgOperation::Difference => self.left.aabb(),
+        }
+    }
+}
+
+#[typetag::serde]
+impl Shape for Csg {
+    fn hit(&self, ray: &Ray) -> Option<HitRecord> {
+        self.hit_with_bound(ray, (0.0, f64::INFINITY))
+    }
+
+    /// The nearest hit may lie before the bound while a further one lies in it.
+    fn hit_with_bound(&self, ray: &Ray, bound: (f64, f64)) -> Option<HitRecord> {
+        self.boundary(ray)
+            .into_iter()
+            .find(|h| h.toi >= bound.0)
+            .filter(|h| h.toi <= bound.1)
+    }
+
+    fn material(&self, hit: &HitRecord) -> Arc<dyn Material> {
+        hit.material.clone().unwrap_or_else(|| self.left.material(hit))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Option<Vec<(HitRecord, HitRecord)>> {
+        Some(super::pair_up(self.boundary(ray)))
+    }
+
+    fn is_closed(&self) -> bool {
+        self.left.is_closed() && self.right.is_closed()
+    }
+
+    fn is_bounded(&self) -> bool {
+        match self.operation {
+            CsgOperation::Union => self.left.is_bounded() && self.right.is_bounded(),
+            CsgOperation::Intersection | CsgOperation::Difference => self.left.is_bounded(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra_glm::DVec3;
+
+    fn sphere(x: f64) -> String {
+        format!(r#"{{"type": "Sphere", "center": [{}, 0, 0], "radius": 1, "material": {{"type": "Diffuse", "color_diffuse": [1, 1, 1]}}}}"#, x)
+    }
+
+    fn csg(operation: &str, right: &str) -> serde_json::Result<Csg> {
+        serde_json::from_str(&format!(r#"{{"operation": "{}", "left": {}, "right": {}}}"#, operation, sphere(0.0), right))
+    }
+
+    fn tois(operation: &str) -> Vec<(f64, f64)> {
+        let ray = Ray::new(DVec3::new(-5.0, 0.0, 0.0), DVec3::new(1.0, 0.0, 0.0));
+        csg(operation, &sphere(1.0)).unwrap().intervals(&ray).unwrap()
+            .iter().map(|(entry, exit)| (entry.toi, exit.toi)).collect()
+    }
+
+    #[test]
+    fn overlapping_spheres_combine_their_intervals() {
+        // the left sphere spans toi 4 to 6 and the right one 5 to 7
+        assert_eq!(tois("Union"), vec![(4.0, 7.0)]);
+        assert_eq!(tois("Intersection"), vec![(5.0, 6.0)]);
+        assert_eq!(tois("Difference"), vec![(4.0, 5.0)]);
+    }
+
+    #[test]
+    fn difference_turns_the_cut_out_surface_inside_out() {
+        let difference = csg("Difference", &sphere(1.0)).unwrap();
+        let ray = Ray::new(DVec3::new(-5.0, 0.0, 0.0), DVec3::new(1.0, 0.0, 0.0));
+        let hit = difference.hit_with_bound(&ray, (4.5, f64::INFINITY)).unwrap();
+        assert_eq!(hit.toi, 5.0);
+        assert!(hit.normal.x > 0.0);
+    }
+
+    #[test]
+    fn open_children_are_rejected() {
+        let plane = r#"{"type": "Plane", "point": [0, 0, 0], "normal": [0, 1, 0], "material": {"type": "Diffuse", "color_diffuse": [1, 1, 1]}}"#;
+        assert!(csg("Union", plane).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use bvh::aabb::Bounded;
+use nalgebra_glm::{DMat4, DVec3, DVec4};
+use nalgebra_glm as glm;
+use serde::{Deserialize, Serialize};
+
+use crate::{hit::{HitRecord, Ray}, material::Material};
+use super::{Shape, aabb_from, nearest_hit, transform_aabb};
+
+/// Unit cube centered at the origin, under `transform`. Unlike `draw_cube`,
+/// this is a single closed shape, so it can be used in CSG.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Cuboid {
+    pub transform: DMat4,
+    pub material: Arc<dyn Material>,
+}
+
+impl Cuboid {
+    /// Entry and exit hits of the ray, including those behind its origin.
+    fn slab_hits(&self, ray: &Ray) -> Option<(HitRecord, HitRecord)> {
+        let inverse = self.transform.try_inverse()?;
+        // The direction is not renormalized, so tois are the same in both spaces.
+        let o = (inverse * DVec4::new(ray.origin.x, ray.origin.y, ray.origin.z, 1.0)).xyz();
+        let d = (inverse * DVec4::new(ray.direction.x, ray.direction.y, ray.direction.z, 0.0)).xyz();
+
+        let (mut t_near, mut t_far) = (f64::NEG_INFINITY, f64::INFINITY);
+        let (mut axis_near, mut axis_far) = (DVec3::zeros(), DVec3::zeros());
+        for axis in 0..3 {
+            let mut unit = DVec3::zeros();
+            unit[axis] = 1.0;
+            if d[axis] == 0.0 {
+                if o[axis].abs() > 0.5 {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (-0.5 - o[axis]) / d[axis];
+            let t1 = (0.5 - o[axis]) / d[axis];
+            let (t0, t1, n0) = if t0 < t1 { (t0, t1, -unit) } else { (t1, t0, unit) };
+            if t0 > t_near {
+                t_near = t0;
+                axis_near = n0;
+            }
+            if t1 < t_far {
+                t_far = t1;
+                axis_far = -n0;
+            }
+        }
+        if t_near > t_far {
+            return None;
+        }
+
+        let normal_matrix = glm::mat4_to_mat3(&inverse.transpose());
+        let make_hit = |toi: f64, local_normal: DVec3| {
+            HitRecord::new(toi, ray.origin + toi * ray.direction, (normal_matrix * local_normal).normalize())
+        };
+        Some((make_hit(t_near, axis_near), make_hit(t_far, axis_far)))
+    }
+}
+
+impl Bounded for Cuboid {
+    fn aabb(&self) -> bvh::aabb::AABB {
+        let unit_cube = aabb_from(&DVec3::from_element(-0.5), &DVec3::from_element(0.5));
+        let (min, max) = transform_aabb(&unit_cube, &self.transform);
+        aabb_from(&min, &max)
+    }
+}
+
+#[typetag::serde]
+impl Shape for Cuboid {
+    fn hit(&self, ray: &Ray) -> Option<HitRecord> {
+        let (entry, exit) = self.slab_hits(ray)?;
+        nearest_hit([entry, exit])
+    }
+
+    fn material(&self, _hit: &HitRecord) -> Arc<dyn Material> {
+        self.material.clone()
+    }
+
+    fn intervals(&self, ray: &Ray) -> Option<Vec<(HitRecord, HitRecord)>> {
+        Some(self.slab_hits(ray).into_iter().collect())
+    }
+
+    fn is_closed(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use bvh::aabb::Bounded;
+use nalgebra_glm::{DVec2, DVec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{hit::{HitRecord, Ray}, material::Material, utils::orthonormal_basis};
+use super::{Shape, aabb_from};
+
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum CurveType {
+    /// A flat strip that always faces the ray, for grass blades and distant hair.
+    Ribbon,
+    /// A strip shaded as if it were round, for hair and fur seen up close.
+    #[default]
+    Tube,
+}
+
+/// A cubic Bézier segment swept with a width varying linearly from `width[0]` to `width[1]`.
+/// Hits carry the tangent, and as uv the curve parameter and the offset across the width,
+/// from 0 on one edge to 1 on the other.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Curve {
+    pub points: [DVec3; 4],
+    pub width: [f64; 2],
+    #[serde(default)]
+    pub curve_type: CurveType,
+    pub material: Arc<dyn Material>,
+}
+
+fn eval_bezier(points: &[DVec3; 4], u: f64) -> (DVec3, DVec3) {
+    let lerp = |a: &DVec3, b: &DVec3| a + (b - a) * u;
+    let (a, b, c) = (lerp(&points[0], &points[1]), lerp(&points[1], &points[2]), lerp(&points[2], &points[3]));
+    let (d, e) = (lerp(&a, &b), lerp(&b, &c));
+    (lerp(&d, &e), 3.0 * (e - d))
+}
+
+/// The two halves of the segment, by de Casteljau's algorithm.
+fn split_bezier(points: &[DVec3; 4]) -> ([DVec3; 4], [DVec3; 4]) {
+    let mid = |a: &DVec3, b: &DVec3| (a + b) / 2.0;
+    let (a, b, c) = (mid(&points[0], &points[1]), mid(&points[1], &points[2]), mid(&points[2], &points[3]));
+    let (d, e) = (mid(&a, &b), mid(&b, &c));
+    let center = mid(&d, &e);
+    ([points[0], a, d, center], [center, e, c, points[3]])
+}
+
+impl Curve {
+    fn width_at(&self, u: f64) -> f64 {
+        self.width[0] + (self.width[1] - self.width[0]) * u
+    }
+
+    /// Nearest toi and curve parameter within `(t_min, t_max)`, where `points` are the
+    /// control points between `u0` and `u1` in the space of a ray starting at the origin along z.
+    fn recursive_hit(&self, points: &[DVec3; 4], u0: f64, u1: f64, depth: u32, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let half_width = self.width_at(u0).max(self.width_at(u1)) / 2.0;
+        let min = points.iter().fold(points[0], |m, p| m.inf(p)).add_scalar(-half_width);
+        let max = points.iter().fold(points[0], |m, p| m.sup(p)).add_scalar(half_width);
+        if min.x > 0.0 || max.x < 0.0 || min.y > 0.0 || max.y < 0.0 || max.z < t_min || min.z > t_max {
+            return None;
+        }
+        if depth > 0 {
+            let (left, right) = split_bezier(points);
+            let middle = (u0 + u1) / 2.0;
+            return match self.recursive_hit(&left, u0, middle, depth - 1, t_min, t_max) {
+                Some((toi, u)) => Some(self.recursive_hit(&right, middle, u1, depth - 1, t_min, toi).unwrap_or((toi, u))),
+                None => self.recursive_hit(&right, middle, u1, depth - 1, t_min, t_max),
+            };
+        }
+
+        // The segment is nearly straight now. Rays past either end belong to the neighbors.
+        let edge = (points[1].y - points[0].y) * -points[0].y + points[0].x * (points[0].x - points[1].x);
+        let edge_end = (points[2].y - points[3].y) * -points[3].y + points[3].x * (points[3].x - points[2].x);
+        if edge < 0.0 || edge_end < 0.0 {
+            return None;
+        }
+        let segment = (points[3] - points[0]).xy();
+        if segment.norm_squared() == 0.0 {
+            return None;
+        }
+        let w = (-points[0].xy().dot(&segment) / segment.norm_squared()).clamp(0.0, 1.0);
+        let u = u0 + (u1 - u0) * w;
+        let (center, _) = eval_bezier(points, w);
+        let half_width = self.width_at(u) / 2.0;
+        if center.xy().norm_squared() > half_width * half_width || center.z < t_min || center.z > t_max {
+            return None;
+        }
+        // Rays spawned on the curve start within half a width of its center. Hits on the part
+        // they leave are skipped; what happens inside of it is left to the material.
+        if center.norm_squared() <= 4.0 * half_width * half_width {
+            return None;
+        }
+        Some((center.z, u))
+    }
+
+    /// Subdivisions needed for the segments to be flat to a fraction of the width.
+    fn max_depth(points: &[DVec3; 4], width: f64) -> u32 {
+        let bend = (0..2)
+            .map(|i| (points[i] - 2.0 * points[i + 1] + points[i + 2]).abs().max())
+            .fold(0.0, f64::max);
+        let tolerance = width * 0.05;
+        if bend <= 0.0 || tolerance <= 0.0 {
+            return 0;
+        }
+        ((std::f64::consts::SQRT_2 * 6.0 * bend / (8.0 * tolerance)).log2().max(0.0) as u32 / 2).min(10)
+    }
+}
+
+impl Bounded for Curve {
+    fn aabb(&self) -> bvh::aabb::AABB {
+        // the curve lies within the hull of its control points
+        let half_width = self.width[0].max(self.width[1]) / 2.0;
+        let min = self.points.iter().fold(self.points[0], |m, p| m.inf(p)).add_scalar(-half_width);
+        let max = self.points.iter().fold(self.points[0], |m, p| m.sup(p)).add_scalar(half_width);
+        aabb_from(&min, &max)
+    }
+}
+
+#[typetag::serde]
+impl Shape for Curve {
+    fn hit(&self, ray: &Ray) -> Option<HitRecord> {
+        self.hit_with_bound(ray, (0.0, f64::INFINITY))
+    }
+
+    fn hit_with_bound(&self, ray: &Ray, bound: (f64, f64)) -> Option<HitRecord> {
+        let speed = ray.direction.norm();
+        let z = ray.direction / speed;
+        let (x, y) = orthonormal_basis(&z);
+        let points = self.points.map(|p| {
+            let p = p - ray.origin;
+            DVec3::new(p.dot(&x), p.dot(&y), p.dot(&z))
+        });
+        let depth = Self::max_depth(&points, self.width[0].max(self.width[1]));
+        let (distance, u) = self.recursive_hit(&points, 0.0, 1.0, depth, bound.0 * speed, bound.1 * speed)?;
+
+        let toi = distance / speed;
+        let point = ray.origin + toi * ray.direction;
+        let (center, derivative) = eval_bezier(&self.points, u);
+        let tangent = derivative.try_normalize(0.0).unwrap_or_else(|| (self.points[3] - self.points[0]).normalize());
+        let facing = (-z - tangent * (-z).dot(&tangent)).try_normalize(1e-12).unwrap_or_else(|| orthonormal_basis(&tangent).0);
+        let side = facing.cross(&tangent);
+        let v = ((point - center).dot(&side) / (self.width_at(u) / 2.0)).clamp(-1.0, 1.0);
+        let normal = match self.curve_type {
+            CurveType::Ribbon => facing,
+            CurveType::Tube => (1.0 - v * v).sqrt() * facing + v * side,
+        };
+        let mut hit = HitRecord::new(toi, point, normal);
+        hit.uv = Some(DVec2::new(u, (v + 1.0) / 2.0));
+        hit.tangent = Some(tangent);
+        Some(hit)
+    }
+
+    fn material(&self, _hit: &HitRecord) -> Arc<dyn Material> {
+        self.material.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Diffuse;
+
+    fn strand(x: f64) -> Curve {
+        let points = [0.0, 1.0, 2.0, 3.0].map(|y| DVec3::new(x, y, 0.0));
+        Curve { points, width: [0.1, 0.1], curve_type: CurveType::Tube, material: Arc::new(Diffuse { color_diffuse: DVec3::repeat(1.0), texture: None }) }
+    }
+
+    #[test]
+    fn rays_leaving_a_strand_skip_it_but_hit_its_neighbor() {
+        let (near, far) = (strand(0.0), strand(0.2));
+        let leaving = Ray::new(DVec3::new(0.05, 1.5, 0.0), DVec3::new(1.0, 0.0, 0.0));
+        assert!(near.hit(&leaving).is_none());
+        let hit = far.hit(&leaving).expect("neighboring strand is hit");
+        assert!((hit.toi - 0.15).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use bvh::aabb::Bounded;
+use nalgebra_glm::DVec3;
+use serde::{Deserialize, Serialize};
+
+use crate::{hit::{HitRecord, Ray}, material::Material, utils::solve_quadratic};
+use super::{Shape, aabb_from, disk_aabb, disk_toi, nearest_hit, pair_up};
+
+/// Cylinder around the segment from `p0` to `p1`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Cylinder {
+    pub p0: DVec3,
+    pub p1: DVec3,
+    pub radius: f64,
+    /// Close both ends with disks. An uncapped cylinder is an open tube.
+    #[serde(default = "default_capped")]
+    pub capped: bool,
+    pub material: Arc<dyn Material>,
+}
+
+pub(super) fn default_capped() -> bool {
+    true
+}
+
+impl Cylinder {
+    /// Every intersection of the ray with the surface, including those behind the origin.
+    pub(super) fn all_hits(&self, ray: &Ray) -> Vec<HitRecord> {
+        let axis = self.p1 - self.p0;
+        let height = axis.norm();
+        let w = axis / height;
+
+        let oc = ray.origin - self.p0;
+        let d_perp = ray.direction - ray.direction.dot(&w) * w;
+        let o_perp = oc - oc.dot(&w) * w;
+
+        let mut hits = solve_quadratic([
+            o_perp.norm_squared() - self.radius * self.radius,
+            2.0 * o_perp.dot(&d_perp),
+            d_perp.norm_squared(),
+        ])
+        .into_iter()
+        .filter_map(|toi| {
+            let point = ray.origin + toi * ray.direction;
+            let y = (point - self.p0).dot(&w);
+            if !(0.0..=height).contains(&y) {
+                return None;
+            }
+            let normal = (point - self.p0 - y * w) / self.radius;
+            Some(HitRecord::new(toi, point, normal))
+        })
+        .collect::<Vec<_>>();
+
+        if self.capped {
+            for (center, normal) in [(self.p0, -w), (self.p1, w)] {
+                if let Some(toi) = disk_toi(ray, &center, &normal, self.radius) {
+                    hits.push(HitRecord::new(toi, ray.origin + toi * ray.direction, normal));
+                }
+            }
+        }
+        hits.sort_by(|a, b| a.toi.partial_cmp(&b.toi).unwrap());
+        hits
+    }
+}
+
+impl Bounded for Cylinder {
+    fn aabb(&self) -> bvh::aabb::AABB {
+        let w = self.p1 - self.p0;
+        let (min0, max0) = disk_aabb(&self.p0, &w, self.radius);
+        let (min1, max1) = disk_aabb(&self.p1, &w, self.radius);
+        aabb_from(&min0, &max0).join(&aabb_from(&min1, &max1))
+    }
+}
+
+#[typetag::serde]
+impl Shape for Cylinder {
+    fn hit(&self, ray: &Ray) -> Option<HitRecord> {
+        nearest_hit(self.all_hits(ray))
+    }
+
+    fn material(&self, _hit: &HitRecord) -> Arc<dyn Material> {
+        self.material.clone()
+    }
+
+    fn intervals(&self, ray: &Ray) -> Option<Vec<(HitRecord, HitRecord)>> {
+        if !(self.capped) {
+            return None;
+        }
+        Some(pair_up(self.all_hits(ray)))
+    }
+
+    fn is_closed(&self) -> bool {
+        self.capped
+    }
+}
@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use bvh::aabb::Bounded;
+use nalgebra_glm as glm;
+use serde::{Deserialize, Serialize};
+
+use crate::{hit::{HitRecord, Ray}, material::Material};
+use super::{Shape, aabb_from, disk_aabb, disk_toi};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Disk {
+    pub center: glm::DVec3,
+    pub normal: glm::DVec3,
+    pub radius: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl Bounded for Disk {
+    fn aabb(&self) -> bvh::aabb::AABB {
+        let (min, max) = disk_aabb(&self.center, &self.normal, self.radius);
+        aabb_from(&min, &max)
+    }
+}
+
+#[typetag::serde]
+impl Shape for Disk {
+    fn hit(&self, ray: &Ray) -> Option<HitRecord> {
+        let normal = self.normal.normalize();
+        let toi = disk_toi(ray, &self.center, &normal, self.radius)?;
+        if toi < 0.0 {
+            return None;
+        }
+        let point = ray.origin + toi * ray.direction;
+        Some(HitRecord::new(toi, point, normal))
+    }
+
+    fn material(&self, _hit: &HitRecord) -> Arc<dyn Material> {
+        self.material.clone()
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use bvh::aabb::{Bounded, AABB};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{hit::{BVHBroadPhase, BroadPhase, BroadPhaseShape, HitRecord, Ray}, material::Material};
+use super::Shape;
+
+/// Several shapes behind their own BVH, so that they can be placed as one by `Instance`.
+#[derive(Serialize, Deserialize)]
+#[serde(from = "GroupShapes")]
+pub struct Group {
+    #[serde(serialize_with = "serialize_shapes")]
+    shapes: Vec<BroadPhaseShape>,
+    #[serde(skip)]
+    broad_phase: BVHBroadPhase,
+}
+
+#[derive(Deserialize)]
+struct GroupShapes {
+    shapes: Vec<Arc<dyn Shape>>,
+}
+
+impl From<GroupShapes> for Group {
+    fn from(group: GroupShapes) -> Self {
+        Group::new(group.shapes)
+    }
+}
+
+fn serialize_shapes<S: Serializer>(shapes: &[BroadPhaseShape], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(shapes.iter().map(|s| &s.shape))
+}
+
+impl Group {
+    pub fn new(shapes: Vec<Arc<dyn Shape>>) -> Self {
+        let mut shapes = shapes.into_iter().map(BroadPhaseShape::new).collect::<Vec<_>>();
+        let mut broad_phase = BVHBroadPhase::default();
+        broad_phase.build(&mut shapes);
+        Self { shapes, broad_phase }
+    }
+}
+
+impl Bounded for Group {
+    fn aabb(&self) -> AABB {
+        self.shapes.iter().fold(AABB::empty(), |aabb, s| aabb.join(&s.shape.aabb()))
+    }
+}
+
+#[typetag::serde]
+impl Shape for Group {
+    fn hit(&self, ray: &Ray) -> Option<HitRecord> {
+        self.hit_with_bound(ray, (0.0, f64::INFINITY))
+    }
+
+    fn hit_with_bound(&self, ray: &Ray, bound: (f64, f64)) -> Option<HitRecord> {
+        self.broad_phase.trace(&self.shapes, ray)
+            .into_iter()
+            .filter_map(|s| s.shape.hit_with_bound(ray, bound).map(|mut hit| {
+                if hit.material.is_none() {
+                    hit.material = Some(s.shape.material(&hit));
+                }
+                hit
+            }))
+            .min_by(|a, b| a.toi.partial_cmp(&b.toi).unwrap())
+    }
+
+    fn material(&self, hit: &HitRecord) -> Arc<dyn Material> {
+        hit.material.clone().expect("hits on a group carry the material of the shape hit")
+    }
+
+    fn is_bounded(&self) -> bool {
+        self.shapes.iter().all(|s| s.shape.is_bounded())
+    }
+}
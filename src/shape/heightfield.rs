@@ -0,0 +1,205 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::{anyhow, ensure};
+use bvh::aabb::Bounded;
+use nalgebra_glm::{DVec2, DVec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{hit::{HitRecord, Ray}, material::Material, utils::scene_path};
+use super::{Shape, aabb_from, intersect_triangle};
+
+/// Terrain from a grayscale image, one sample per pixel, white the highest.
+/// The image spans `size` along x and z around `center`, top rows towards -z,
+/// and rises `height` above it at white. The path is relative to the scene file.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HeightfieldData {
+    pub path: PathBuf,
+    pub center: DVec3,
+    pub size: DVec2,
+    pub height: f64,
+    pub material: Arc<dyn Material>,
+}
+
+/// Each cell between four samples is split into two triangles, which rays visit
+/// by walking the grid, so that the terrain is never expanded into separate shapes.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "HeightfieldData", into = "HeightfieldData")]
+pub struct Heightfield {
+    data: HeightfieldData,
+    width: usize,
+    depth: usize,
+    /// Sample heights above the center, row by row.
+    heights: Vec<f64>,
+    normals: Vec<DVec3>,
+    /// Highest of the four corners of each cell.
+    cell_max: Vec<f64>,
+    min_height: f64,
+    max_height: f64,
+}
+
+impl TryFrom<HeightfieldData> for Heightfield {
+    type Error = anyhow::Error;
+
+    fn try_from(data: HeightfieldData) -> anyhow::Result<Self> {
+        let image = image::open(scene_path(&data.path))
+            .map_err(|e| anyhow!("failed to load heightfield {}: {}", data.path.display(), e))?
+            .to_luma32f();
+        let (width, depth) = (image.width() as usize, image.height() as usize);
+        ensure!(width >= 2 && depth >= 2, "heightfield {} needs at least 2x2 pixels", data.path.display());
+        let heights = image.pixels().map(|p| p.0[0] as f64 * data.height).collect::<Vec<_>>();
+
+        let cell = Self::cell_size(&data, width, depth);
+        let at = |i: usize, j: usize| heights[j * width + i];
+        let normals = (0..depth).flat_map(|j| (0..width).map(move |i| (i, j))).map(|(i, j)| {
+            let (i0, i1) = (i.saturating_sub(1), (i + 1).min(width - 1));
+            let (j0, j1) = (j.saturating_sub(1), (j + 1).min(depth - 1));
+            let slope_x = (at(i1, j) - at(i0, j)) / ((i1 - i0) as f64 * cell.x);
+            let slope_z = (at(i, j1) - at(i, j0)) / ((j1 - j0) as f64 * cell.y);
+            DVec3::new(-slope_x, 1.0, -slope_z).normalize()
+        }).collect();
+        let cell_max = (0..depth - 1).flat_map(|j| (0..width - 1).map(move |i| (i, j))).map(|(i, j)| {
+            at(i, j).max(at(i + 1, j)).max(at(i, j + 1)).max(at(i + 1, j + 1))
+        }).collect();
+        let min_height = heights.iter().copied().fold(f64::INFINITY, f64::min);
+        let max_height = heights.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+        Ok(Self { data, width, depth, heights, normals, cell_max, min_height, max_height })
+    }
+}
+
+impl From<Heightfield> for HeightfieldData {
+    fn from(heightfield: Heightfield) -> Self {
+        heightfield.data
+    }
+}
+
+impl Heightfield {
+    fn cell_size(data: &HeightfieldData, width: usize, depth: usize) -> DVec2 {
+        DVec2::new(data.size.x / (width - 1) as f64, data.size.y / (depth - 1) as f64)
+    }
+
+    /// Corner of the grid at the lowest x and z.
+    fn origin(&self) -> DVec3 {
+        self.data.center - DVec3::new(self.data.size.x, 0.0, self.data.size.y) / 2.0
+    }
+
+    fn bounds(&self) -> (DVec3, DVec3) {
+        let origin = self.origin();
+        let min = origin + DVec3::new(0.0, self.min_height.min(0.0), 0.0);
+        let max = origin + DVec3::new(self.data.size.x, self.max_height.max(0.0), self.data.size.y);
+        (min, max)
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> DVec3 {
+        let cell = Self::cell_size(&self.data, self.width, self.depth);
+        self.origin() + DVec3::new(i as f64 * cell.x, self.heights[j * self.width + i], j as f64 * cell.y)
+    }
+
+    /// Nearest hit within `bound` on the two triangles of cell (i, j).
+    fn hit_cell(&self, ray: &Ray, i: usize, j: usize, bound: (f64, f64)) -> Option<HitRecord> {
+        let corners = [(i, j), (i, j + 1), (i + 1, j + 1), (i + 1, j)];
+        [[0, 1, 2], [0, 2, 3]].into_iter().filter_map(|triangle| {
+            let samples = triangle.map(|k| corners[k]);
+            let points = samples.map(|(i, j)| self.vertex(i, j));
+            let (toi, (k1, k2)) = intersect_triangle(ray, &points)?;
+            if toi < bound.0 || toi > bound.1 {
+                return None;
+            }
+            let weights = [1.0 - k1 - k2, k1, k2];
+            let normal = (0..3).map(|k| weights[k] * self.normals[samples[k].1 * self.width + samples[k].0]).sum::<DVec3>();
+            let uv = (0..3).map(|k| weights[k] * DVec2::new(
+                samples[k].0 as f64 / (self.width - 1) as f64,
+                1.0 - samples[k].1 as f64 / (self.depth - 1) as f64,
+            )).sum::<DVec2>();
+            let mut hit = HitRecord::new(toi, ray.origin + toi * ray.direction, normal.normalize());
+            hit.uv = Some(uv);
+            Some(hit)
+        }).min_by(|a, b| a.toi.partial_cmp(&b.toi).unwrap())
+    }
+}
+
+impl Bounded for Heightfield {
+    fn aabb(&self) -> bvh::aabb::AABB {
+        let (min, max) = self.bounds();
+        aabb_from(&min, &max)
+    }
+}
+
+#[typetag::serde]
+impl Shape for Heightfield {
+    fn hit(&self, ray: &Ray) -> Option<HitRecord> {
+        self.hit_with_bound(ray, (0.0, f64::INFINITY))
+    }
+
+    /// Walk the cells under the ray with a 2D DDA, skipping those the ray passes above.
+    fn hit_with_bound(&self, ray: &Ray, bound: (f64, f64)) -> Option<HitRecord> {
+        let (min, max) = self.bounds();
+        let (mut t_start, mut t_end) = bound;
+        for axis in 0..3 {
+            if ray.direction[axis] == 0.0 {
+                if ray.origin[axis] < min[axis] || ray.origin[axis] > max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (min[axis] - ray.origin[axis]) / ray.direction[axis];
+            let t1 = (max[axis] - ray.origin[axis]) / ray.direction[axis];
+            t_start = t_start.max(t0.min(t1));
+            t_end = t_end.min(t0.max(t1));
+        }
+        if t_start > t_end {
+            return None;
+        }
+
+        let origin = self.origin();
+        let cell = Self::cell_size(&self.data, self.width, self.depth);
+        let entry = ray.origin + t_start * ray.direction - origin;
+        let mut index = [
+            ((entry.x / cell.x).floor().max(0.0) as usize).min(self.width - 2),
+            ((entry.z / cell.y).floor().max(0.0) as usize).min(self.depth - 2),
+        ];
+        let limits = [self.width - 2, self.depth - 2];
+        let (direction, size) = ([ray.direction.x, ray.direction.z], [cell.x, cell.y]);
+        let origin_2d = [ray.origin.x - origin.x, ray.origin.z - origin.z];
+        // toi at which the ray crosses into the next cell along each axis
+        let mut t_next = [0.0; 2];
+        let mut t_delta = [f64::INFINITY; 2];
+        for axis in 0..2 {
+            t_next[axis] = if direction[axis] > 0.0 {
+                ((index[axis] + 1) as f64 * size[axis] - origin_2d[axis]) / direction[axis]
+            } else if direction[axis] < 0.0 {
+                (index[axis] as f64 * size[axis] - origin_2d[axis]) / direction[axis]
+            } else {
+                f64::INFINITY
+            };
+            if direction[axis] != 0.0 {
+                t_delta[axis] = size[axis] / direction[axis].abs();
+            }
+        }
+
+        let mut t_cell = t_start;
+        loop {
+            let t_exit = t_next[0].min(t_next[1]).min(t_end);
+            let lowest = ray.origin.y + ray.direction.y * if ray.direction.y < 0.0 { t_exit } else { t_cell };
+            if lowest <= origin.y + self.cell_max[index[1] * (self.width - 1) + index[0]] {
+                if let Some(hit) = self.hit_cell(ray, index[0], index[1], bound) {
+                    return Some(hit);
+                }
+            }
+            if t_exit >= t_end {
+                return None;
+            }
+            let axis = if t_next[0] < t_next[1] { 0 } else { 1 };
+            if (direction[axis] > 0.0 && index[axis] == limits[axis]) || (direction[axis] < 0.0 && index[axis] == 0) {
+                return None;
+            }
+            index[axis] = if direction[axis] > 0.0 { index[axis] + 1 } else { index[axis] - 1 };
+            t_cell = t_next[axis];
+            t_next[axis] += t_delta[axis];
+        }
+    }
+
+    fn material(&self, _hit: &HitRecord) -> Arc<dyn Material> {
+        self.data.material.clone()
+    }
+}
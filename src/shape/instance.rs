@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use bvh::aabb::{Bounded, AABB};
+use nalgebra_glm::{DMat3, DMat4, DQuat, DVec3, DVec4};
+use nalgebra_glm as glm;
+use serde::{Deserialize, Serialize};
+
+use crate::{hit::{HitRecord, Ray}, material::Material};
+use super::{Shape, aabb_from, transform_aabb};
+
+/// Poses at which the bounding box of a moving instance is taken.
+const MOTION_BOUND_STEPS: usize = 32;
+
+/// A shape placed by `transform`, sharing its geometry with other instances of it.
+/// Rays are transformed into the object space of `shape` instead of the shape into the world.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "InstanceData", into = "InstanceData")]
+pub struct Instance {
+    shape: Arc<dyn Shape>,
+    transform: DMat4,
+    transform_end: Option<DMat4>,
+    material: Option<Arc<dyn Material>>,
+    /// Inverse of `transform` if the instance does not move.
+    inverse: Option<DMat4>,
+    /// `transform` and `transform_end` taken apart for interpolation if it does.
+    motion: Option<(Decomposed, Decomposed)>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct InstanceData {
+    shape: Arc<dyn Shape>,
+    transform: DMat4,
+    /// Transform at time 1. The instance is static if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    transform_end: Option<DMat4>,
+    /// Replaces the materials of `shape` when given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    material: Option<Arc<dyn Material>>,
+}
+
+impl From<InstanceData> for Instance {
+    fn from(data: InstanceData) -> Self {
+        Instance::new(data.shape, data.transform)
+            .with_transform_end(data.transform_end)
+            .with_material(data.material)
+    }
+}
+
+impl From<Instance> for InstanceData {
+    fn from(instance: Instance) -> Self {
+        InstanceData {
+            shape: instance.shape,
+            transform: instance.transform,
+            transform_end: instance.transform_end,
+            material: instance.material,
+        }
+    }
+}
+
+/// An affine transform split into translation, rotation and a symmetric stretch,
+/// so that moving instances turn instead of shearing on their way.
+#[derive(Clone, Copy)]
+struct Decomposed {
+    translation: DVec3,
+    rotation: DQuat,
+    stretch: DMat3,
+}
+
+impl Decomposed {
+    /// Polar decomposition of the linear part, after Shoemake and Duff, "Matrix Animation and Polar Decomposition".
+    fn new(transform: &DMat4) -> Self {
+        let linear = glm::mat4_to_mat3(transform);
+        let svd = linear.svd(true, true);
+        let mut rotation = svd.u.unwrap() * svd.v_t.unwrap();
+        // mirroring stays in the stretch so that the rotation is proper
+        if rotation.determinant() < 0.0 {
+            rotation = -rotation;
+        }
+        Self {
+            translation: transform.column(3).xyz(),
+            rotation: glm::mat3_to_quat(&rotation),
+            stretch: rotation.transpose() * linear,
+        }
+    }
+
+    fn interpolate(&self, other: &Self, t: f64) -> DMat4 {
+        let translation = glm::lerp(&self.translation, &other.translation, t);
+        let rotation = slerp(&self.rotation, &other.rotation, t);
+        let stretch = self.stretch + (other.stretch - self.stretch) * t;
+        glm::translation(&translation) * glm::quat_to_mat4(&rotation) * glm::mat3_to_mat4(&stretch)
+    }
+}
+
+/// Spherical interpolation along the shorter arc, falling back to a normalised lerp for close rotations.
+fn slerp(a: &DQuat, b: &DQuat, t: f64) -> DQuat {
+    let b = if a.dot(b) < 0.0 { -b } else { *b };
+    let cos_angle = a.dot(&b).min(1.0);
+    if cos_angle > 0.9995 {
+        return glm::quat_normalize(&glm::quat_lerp(a, &b, t));
+    }
+    let angle = cos_angle.acos();
+    (a * ((1.0 - t) * angle).sin() + b * (t * angle).sin()) * (1.0 / angle.sin())
+}
+
+impl Instance {
+    pub fn new(shape: Arc<dyn Shape>, transform: DMat4) -> Self {
+        Self { shape, transform, transform_end: None, material: None, inverse: transform.try_inverse(), motion: None }
+    }
+
+    /// Move the instance to `transform_end` at time 1. It stays static if None.
+    pub fn with_transform_end(self, transform_end: Option<DMat4>) -> Self {
+        let (inverse, motion) = match transform_end {
+            Some(ref end) => (None, Some((Decomposed::new(&self.transform), Decomposed::new(end)))),
+            None => (self.transform.try_inverse(), None),
+        };
+        Self { transform_end, inverse, motion, ..self }
+    }
+
+    /// Replace the materials of the shape if given.
+    pub fn with_material(self, material: Option<Arc<dyn Material>>) -> Self {
+        Self { material, ..self }
+    }
+
+    fn transform_at(&self, time: f64) -> DMat4 {
+        match self.motion {
+            Some((ref start, ref end)) => start.interpolate(end, time.clamp(0.0, 1.0)),
+            None => self.transform,
+        }
+    }
+
+    /// The ray in the object space of `shape`, the factor by which its tois are scaled,
+    /// and the inverse transform.
+    fn local_ray(&self, ray: &Ray) -> Option<(Ray, f64, DMat4)> {
+        let inverse = match self.inverse {
+            Some(inverse) => inverse,
+            None => self.transform_at(ray.time).try_inverse()?,
+        };
+        let origin = (inverse * DVec4::new(ray.origin.x, ray.origin.y, ray.origin.z, 1.0)).xyz();
+        let direction = (inverse * DVec4::new(ray.direction.x, ray.direction.y, ray.direction.z, 0.0)).xyz();
+        // Shapes expect unit directions, so tois are scaled between the two spaces.
+        let scale = direction.norm();
+        Some((Ray::new(origin, direction).with_time(ray.time), scale, inverse))
+    }
+
+    fn world_hit(&self, ray: &Ray, local_hit: &HitRecord, scale: f64, inverse: &DMat4) -> HitRecord {
+        let toi = local_hit.toi / scale;
+        let normal = (glm::mat4_to_mat3(&inverse.transpose()) * local_hit.normal).normalize();
+        let mut hit = HitRecord::new(toi, ray.origin + toi * ray.direction, normal);
+        hit.uv = local_hit.uv;
+        hit.color = local_hit.color;
+        hit.tangent = local_hit.tangent.map(|t| (self.transform_at(ray.time) * DVec4::new(t.x, t.y, t.z, 0.0)).xyz().normalize());
+        hit.material = self.material.clone().or_else(|| Some(self.shape.material(local_hit)));
+        hit
+    }
+}
+
+impl Bounded for Instance {
+    fn aabb(&self) -> AABB {
+        let local = self.shape.aabb();
+        // a turning instance can leave the boxes of its end poses, so the motion is sampled
+        let steps = if self.motion.is_some() { MOTION_BOUND_STEPS } else { 0 };
+        (0..=steps).fold(AABB::empty(), |aabb, i| {
+            let time = if steps == 0 { 0.0 } else { i as f64 / steps as f64 };
+            let (min, max) = transform_aabb(&local, &self.transform_at(time));
+            aabb.join(&aabb_from(&min, &max))
+        })
+    }
+}
+
+#[typetag::serde]
+impl Shape for Instance {
+    fn hit(&self, ray: &Ray) -> Option<HitRecord> {
+        self.hit_with_bound(ray, (0.0, f64::INFINITY))
+    }
+
+    fn hit_with_bound(&self, ray: &Ray, bound: (f64, f64)) -> Option<HitRecord> {
+        let (local_ray, scale, inverse) = self.local_ray(ray)?;
+        let local_hit = self.shape.hit_with_bound(&local_ray, (bound.0 * scale, bound.1 * scale))?;
+        Some(self.world_hit(ray, &local_hit, scale, &inverse))
+    }
+
+    fn material(&self, hit: &HitRecord) -> Arc<dyn Material> {
+        hit.material.clone().expect("hits on an instance carry the material of the shape hit")
+    }
+
+    fn intervals(&self, ray: &Ray) -> Option<Vec<(HitRecord, HitRecord)>> {
+        let (local_ray, scale, inverse) = self.local_ray(ray)?;
+        let intervals = self.shape.intervals(&local_ray)?;
+        Some(intervals.iter().map(|(entry, exit)| (
+            self.world_hit(ray, entry, scale, &inverse),
+            self.world_hit(ray, exit, scale, &inverse),
+        )).collect())
+    }
+
+    fn is_closed(&self) -> bool {
+        self.shape.is_closed()
+    }
+
+    fn is_bounded(&self) -> bool {
+        self.shape.is_bounded()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Diffuse, shape::Sphere};
+
+    fn sphere() -> Arc<dyn Shape> {
+        Arc::new(Sphere::new(DVec3::zeros(), 1.0, Arc::new(Diffuse::new(DVec3::repeat(0.5)))))
+    }
+
+    #[test]
+    fn decomposition_round_trips() {
+        let transform = glm::translation(&DVec3::new(1.0, 2.0, 3.0))
+            * glm::rotation(0.7, &DVec3::new(1.0, 1.0, 0.0))
+            * glm::scaling(&DVec3::new(2.0, -0.5, 1.5));
+        let decomposed = Decomposed::new(&transform);
+        assert!((decomposed.interpolate(&decomposed, 0.3) - transform).norm() < 1e-9);
+    }
+
+    #[test]
+    fn moving_instance_turns_without_shrinking() {
+        let start = glm::translation(&DVec3::new(0.0, 0.0, -5.0));
+        let end = start * glm::rotation(std::f64::consts::FRAC_PI_2, &DVec3::y());
+        let instance = Instance::new(sphere(), start).with_transform_end(Some(end));
+        let halfway = instance.transform_at(0.5);
+        let expected = start * glm::rotation(std::f64::consts::FRAC_PI_4, &DVec3::y());
+        assert!((halfway - expected).norm() < 1e-9);
+    }
+
+    #[test]
+    fn static_instance_hits_through_cached_inverse() {
+        let instance = Instance::new(sphere(), glm::translation(&DVec3::new(0.0, 0.0, -5.0)) * glm::scaling(&DVec3::repeat(2.0)));
+        let hit = instance.hit(&Ray::new(DVec3::zeros(), DVec3::new(0.0, 0.0, -1.0))).unwrap();
+        assert!((hit.toi - 3.0).abs() < 1e-9);
+        assert!((hit.normal - DVec3::z()).norm() < 1e-9);
+    }
+}
@@ -0,0 +1,212 @@
+use std::{fs, path::Path, sync::Arc};
+
+use anyhow::{bail, ensure, Context};
+
+use bvh::{aabb::{Bounded, AABB}, bounding_hierarchy::BHShape, bvh::BVH};
+use nalgebra_glm::{DVec2, DVec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{hit::{HitRecord, Ray}, material::Material};
+use super::{Shape, aabb_from, load_obj, ply_file::load_ply, stl_file::load_stl, triangle::intersect_triangle};
+
+/// Indexed triangles sharing their vertices. Normals, UVs and colors are given per vertex and
+/// interpolated over each face; without normals the mesh is flat shaded.
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "MeshData")]
+pub struct TriangleMesh {
+    pub positions: Vec<DVec3>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub normals: Vec<DVec3>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub uvs: Vec<DVec2>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub colors: Vec<DVec3>,
+    pub indices: Vec<[usize; 3]>,
+    /// Material of faces without one in `face_materials`.
+    pub material: Arc<dyn Material>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub materials: Vec<Arc<dyn Material>>,
+    /// Index into `materials` for each face, if the faces have their own materials.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub face_materials: Vec<Option<usize>>,
+    #[serde(skip)]
+    faces: Vec<Face>,
+    #[serde(skip)]
+    bvh: Option<BVH>,
+}
+
+#[derive(Deserialize)]
+struct MeshData {
+    positions: Vec<DVec3>,
+    #[serde(default)]
+    normals: Vec<DVec3>,
+    #[serde(default)]
+    uvs: Vec<DVec2>,
+    #[serde(default)]
+    colors: Vec<DVec3>,
+    indices: Vec<[usize; 3]>,
+    material: Arc<dyn Material>,
+    #[serde(default)]
+    materials: Vec<Arc<dyn Material>>,
+    #[serde(default)]
+    face_materials: Vec<Option<usize>>,
+}
+
+impl TryFrom<MeshData> for TriangleMesh {
+    type Error = anyhow::Error;
+
+    fn try_from(data: MeshData) -> anyhow::Result<Self> {
+        TriangleMesh::new(data.positions, data.normals, data.uvs, data.indices, data.material)?
+            .with_colors(data.colors)?
+            .with_face_materials(data.materials, data.face_materials)
+    }
+}
+
+/// A face in the BVH of its mesh.
+struct Face {
+    index: usize,
+    aabb: AABB,
+    node_index: usize,
+}
+
+impl Bounded for Face {
+    fn aabb(&self) -> AABB {
+        self.aabb
+    }
+}
+
+impl BHShape for Face {
+    fn set_bh_node_index(&mut self, i: usize) {
+        self.node_index = i
+    }
+
+    fn bh_node_index(&self) -> usize {
+        self.node_index
+    }
+}
+
+impl TriangleMesh {
+    /// `normals` and `uvs` are either empty or given for every position.
+    pub fn new(positions: Vec<DVec3>, normals: Vec<DVec3>, uvs: Vec<DVec2>, indices: Vec<[usize; 3]>, material: Arc<dyn Material>) -> anyhow::Result<Self> {
+        ensure!(normals.is_empty() || normals.len() == positions.len(),
+            "mesh has {} normals for {} positions", normals.len(), positions.len());
+        ensure!(uvs.is_empty() || uvs.len() == positions.len(),
+            "mesh has {} UVs for {} positions", uvs.len(), positions.len());
+        if let Some(i) = indices.iter().flatten().find(|&&i| i >= positions.len()) {
+            bail!("mesh index {} is out of range for {} positions", i, positions.len());
+        }
+        let mut faces = indices.iter().enumerate().map(|(index, face)| {
+            let points = face.map(|i| positions[i]);
+            let min = points.iter().fold(points[0], |m, p| m.inf(p));
+            let max = points.iter().fold(points[0], |m, p| m.sup(p));
+            Face { index, aabb: aabb_from(&min, &max), node_index: 0 }
+        }).collect::<Vec<_>>();
+        let bvh = if faces.is_empty() { None } else { Some(BVH::build(&mut faces)) };
+        Ok(Self { positions, normals, uvs, colors: vec![], indices, material, materials: vec![], face_materials: vec![], faces, bvh })
+    }
+
+    /// Give each vertex a color, which multiplies that of diffuse materials.
+    pub fn with_colors(self, colors: Vec<DVec3>) -> anyhow::Result<Self> {
+        ensure!(colors.is_empty() || colors.len() == self.positions.len(),
+            "mesh has {} colors for {} positions", colors.len(), self.positions.len());
+        Ok(Self { colors, ..self })
+    }
+
+    /// Give each face the material in `materials` at its index in `face_materials`.
+    pub fn with_face_materials(self, materials: Vec<Arc<dyn Material>>, face_materials: Vec<Option<usize>>) -> anyhow::Result<Self> {
+        ensure!(face_materials.is_empty() || face_materials.len() == self.indices.len(),
+            "mesh has {} face materials for {} faces", face_materials.len(), self.indices.len());
+        if let Some(i) = face_materials.iter().flatten().find(|&&i| i >= materials.len()) {
+            bail!("mesh material index {} is out of range for {} materials", i, materials.len());
+        }
+        Ok(Self { materials, face_materials, ..self })
+    }
+
+    fn hit_face(&self, ray: &Ray, face: usize) -> Option<HitRecord> {
+        let [i0, i1, i2] = self.indices[face];
+        let points = [self.positions[i0], self.positions[i1], self.positions[i2]];
+        let (toi, (k1, k2)) = intersect_triangle(ray, &points)?;
+        let k0 = 1.0 - k1 - k2;
+        let normal = if self.normals.is_empty() {
+            (points[1] - points[0]).cross(&(points[2] - points[0]))
+        } else {
+            k0 * self.normals[i0] + k1 * self.normals[i1] + k2 * self.normals[i2]
+        };
+        let mut hit = HitRecord::new(toi, ray.origin + toi * ray.direction, normal.normalize());
+        if !self.uvs.is_empty() {
+            hit.uv = Some(k0 * self.uvs[i0] + k1 * self.uvs[i1] + k2 * self.uvs[i2]);
+        }
+        if !self.colors.is_empty() {
+            hit.color = Some(k0 * self.colors[i0] + k1 * self.colors[i1] + k2 * self.colors[i2]);
+        }
+        if let Some(&Some(material)) = self.face_materials.get(face) {
+            hit.material = Some(self.materials[material].clone());
+        }
+        Some(hit)
+    }
+}
+
+impl Bounded for TriangleMesh {
+    fn aabb(&self) -> AABB {
+        self.faces.iter().fold(AABB::empty(), |aabb, f| aabb.join(&f.aabb))
+    }
+}
+
+#[typetag::serde]
+impl Shape for TriangleMesh {
+    fn hit(&self, ray: &Ray) -> Option<HitRecord> {
+        self.hit_with_bound(ray, (0.0, f64::INFINITY))
+    }
+
+    fn hit_with_bound(&self, ray: &Ray, bound: (f64, f64)) -> Option<HitRecord> {
+        self.bvh.as_ref()?
+            .traverse(&ray.into(), &self.faces)
+            .into_iter()
+            .filter_map(|f| self.hit_face(ray, f.index))
+            .filter(|h| h.toi >= bound.0 && h.toi <= bound.1)
+            .min_by(|a, b| a.toi.partial_cmp(&b.toi).unwrap())
+    }
+
+    fn material(&self, hit: &HitRecord) -> Arc<dyn Material> {
+        hit.material.clone().unwrap_or_else(|| self.material.clone())
+    }
+}
+
+/// Load an OBJ, PLY or STL file, chosen by extension. Faces without a material of their own get `material`.
+pub fn load_mesh_file(path: &Path, material: Arc<dyn Material>) -> anyhow::Result<TriangleMesh> {
+    let buffer = fs::read(path).with_context(|| format!("failed to read mesh {}", path.display()))?;
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+    let mesh = match extension.as_str() {
+        "obj" => load_obj(&buffer, path.parent(), material),
+        "ply" => load_ply(&buffer, material),
+        "stl" => load_stl(&buffer, material),
+        _ => bail!("unknown mesh format of {}, expected .obj, .ply or .stl", path.display()),
+    };
+    mesh.with_context(|| format!("failed to load mesh {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mesh_json(indices: &str) -> String {
+        format!(r#"{{
+            "type": "TriangleMesh",
+            "positions": [[0, 0, 0], [1, 0, 0], [0, 1, 0]],
+            "indices": {},
+            "material": {{"type": "Diffuse", "color_diffuse": [0.5, 0.5, 0.5]}}
+        }}"#, indices)
+    }
+
+    #[test]
+    fn mesh_from_json_is_hit() {
+        let mesh: Arc<dyn Shape> = serde_json::from_str(&mesh_json("[[0, 1, 2]]")).unwrap();
+        let hit = mesh.hit(&Ray::new(DVec3::new(0.2, 0.2, 1.0), DVec3::new(0.0, 0.0, -1.0))).unwrap();
+        assert!((hit.toi - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn out_of_range_index_is_an_error() {
+        assert!(serde_json::from_str::<Arc<dyn Shape>>(&mesh_json("[[0, 1, 3]]")).is_err());
+    }
+}
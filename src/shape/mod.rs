@@ -1,11 +1,38 @@
+mod cone;
+mod csg;
+mod cuboid;
+mod curve;
+mod cylinder;
+mod disk;
+mod group;
+mod heightfield;
+mod instance;
+mod mesh;
+mod obj_file;
+mod ply_file;
+mod plane;
+mod quad;
+mod refine;
+mod sdf;
 mod sphere;
+mod stl_file;
+mod torus;
 mod triangle;
 
 use std::sync::Arc;
 
 use crate::{hit::{HitRecord, Ray}, material::Material};
-use bvh::aabb::Bounded;
+use bvh::aabb::{Bounded, AABB};
+use itertools::Itertools;
+use nalgebra_glm::DVec3;
+use nalgebra_glm as glm;
 
+pub use curve::{Curve, CurveType};
+pub use group::Group;
+pub use instance::Instance;
+pub use mesh::{TriangleMesh, load_mesh_file};
+pub use obj_file::load_obj;
+pub use refine::refine_mesh;
 pub use sphere::Sphere;
 pub use triangle::*;
 
@@ -24,4 +51,101 @@ pub trait Shape: Bounded + Send + Sync {
     }
 
     fn material(&self, hit: &HitRecord) -> Arc<dyn Material>;
-}
\ No newline at end of file
+
+    /// Sorted (entry, exit) pairs of hits between which the ray is inside the shape,
+    /// including those behind the ray origin.
+    /// Only closed shapes can tell; others return None and cannot be used in CSG.
+    fn intervals(&self, _ray: &Ray) -> Option<Vec<(HitRecord, HitRecord)>> {
+        None
+    }
+
+    /// Whether the shape encloses a volume and so reports `intervals`.
+    fn is_closed(&self) -> bool {
+        false
+    }
+
+    /// Whether `aabb()` encloses the shape. Unbounded shapes such as infinite planes
+    /// are kept out of the BVH and tested against every ray.
+    fn is_bounded(&self) -> bool {
+        true
+    }
+}
+
+/// Position at `time` of a point moving from `start` at time 0 to `end` at time 1.
+pub(crate) fn position_at(start: &DVec3, end: &Option<DVec3>, time: f64) -> DVec3 {
+    match end {
+        Some(end) => glm::lerp(start, end, time.clamp(0.0, 1.0)),
+        None => *start,
+    }
+}
+
+pub(crate) fn aabb_from(min: &DVec3, max: &DVec3) -> AABB {
+    AABB::with_bounds(
+        bvh::Point3::new(min.x as f32, min.y as f32, min.z as f32),
+        bvh::Point3::new(max.x as f32, max.y as f32, max.z as f32),
+    )
+}
+
+/// Bounding box of `aabb` after `transform`, as (min, max).
+pub(crate) fn transform_aabb(aabb: &AABB, transform: &glm::DMat4) -> (DVec3, DVec3) {
+    let corners = (0..8)
+        .map(|i| {
+            let corner = DVec3::new(
+                if i & 1 == 0 { aabb.min.x } else { aabb.max.x } as f64,
+                if i & 2 == 0 { aabb.min.y } else { aabb.max.y } as f64,
+                if i & 4 == 0 { aabb.min.z } else { aabb.max.z } as f64,
+            );
+            (transform * glm::DVec4::new(corner.x, corner.y, corner.z, 1.0)).xyz()
+        })
+        .collect_vec();
+    let min = corners.iter().fold(corners[0], |m, p| glm::min2(&m, p));
+    let max = corners.iter().fold(corners[0], |m, p| glm::max2(&m, p));
+    (min, max)
+}
+
+/// Toi of the ray with the plane through `point` with `normal`, if the ray is not parallel to it.
+pub(crate) fn plane_toi(ray: &Ray, point: &DVec3, normal: &DVec3) -> Option<f64> {
+    let denom = ray.direction.dot(normal);
+    if denom.abs() < 1e-12 {
+        return None;
+    }
+    Some((point - ray.origin).dot(normal) / denom)
+}
+
+/// Toi of the ray with the disk at `center` with unit `normal`, if any.
+pub(crate) fn disk_toi(ray: &Ray, center: &DVec3, normal: &DVec3, radius: f64) -> Option<f64> {
+    let toi = plane_toi(ray, center, normal)?;
+    let point = ray.origin + toi * ray.direction;
+    if (point - center).norm_squared() > radius * radius {
+        return None;
+    }
+    Some(toi)
+}
+
+/// The hit with the smallest non-negative toi.
+pub(crate) fn nearest_hit(hits: impl IntoIterator<Item = HitRecord>) -> Option<HitRecord> {
+    hits.into_iter()
+        .filter(|h| h.toi >= 0.0)
+        .min_by(|a, b| a.toi.partial_cmp(&b.toi).unwrap())
+}
+
+/// Pair up the sorted hits of a closed surface into (entry, exit) intervals.
+/// An odd number of hits means a root was lost or a tangent root kept,
+/// so the pairing cannot be trusted and no intervals are reported.
+pub(crate) fn pair_up(hits: Vec<HitRecord>) -> Vec<(HitRecord, HitRecord)> {
+    if !hits.len().is_multiple_of(2) {
+        return vec![];
+    }
+    hits.into_iter().tuples().collect()
+}
+
+/// Bounding box of a disk, which is also used for the caps of cylinders and cones.
+pub(crate) fn disk_aabb(center: &DVec3, normal: &DVec3, radius: f64) -> (DVec3, DVec3) {
+    let n = normal.normalize();
+    let extent = DVec3::new(
+        radius * (1.0 - n.x * n.x).max(0.0).sqrt(),
+        radius * (1.0 - n.y * n.y).max(0.0).sqrt(),
+        radius * (1.0 - n.z * n.z).max(0.0).sqrt(),
+    );
+    (center - extent, center + extent)
+}
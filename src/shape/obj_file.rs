@@ -0,0 +1,131 @@
+use std::{collections::HashMap, fs::File, io::BufReader, path::Path, sync::Arc};
+
+use itertools::Itertools;
+use nalgebra_glm::{DVec2, DVec3};
+use obj::raw::{material::{Material as MtlMaterial, MtlColor}, object::Polygon, parse_mtl, parse_obj};
+
+use crate::material::{Dielectric, Diffuse, ImageTexture, Light, Material, Metal};
+use super::TriangleMesh;
+
+/// Parse an OBJ file into a mesh. Polygons are split into triangle fans.
+/// Normals and UVs are kept only when every face has them.
+///
+/// With `mtl_dir`, the material libraries the file refers to are read from there and
+/// faces get the materials named by `usemtl`. Other faces get `material`.
+pub fn load_obj(buffer: &[u8], mtl_dir: Option<&Path>, material: Arc<dyn Material>) -> anyhow::Result<TriangleMesh> {
+    let obj = parse_obj(BufReader::new(buffer))?;
+    let corners = |polygon: &Polygon| -> Vec<(usize, Option<usize>, Option<usize>)> {
+        match polygon {
+            Polygon::P(p) => p.iter().map(|&p| (p, None, None)).collect(),
+            Polygon::PT(pt) => pt.iter().map(|&(p, t)| (p, Some(t), None)).collect(),
+            Polygon::PN(pn) => pn.iter().map(|&(p, n)| (p, None, Some(n))).collect(),
+            Polygon::PTN(ptn) => ptn.iter().map(|&(p, t, n)| (p, Some(t), Some(n))).collect(),
+        }
+    };
+    let polygons = obj.polygons.iter().map(corners).collect::<Vec<_>>();
+    let has_normals = polygons.iter().flatten().all(|c| c.2.is_some());
+    let has_uvs = polygons.iter().flatten().all(|c| c.1.is_some());
+
+    // OBJ indexes positions, UVs and normals separately; the mesh needs one index per vertex
+    let mut vertex_indices = HashMap::new();
+    let (mut positions, mut normals, mut uvs) = (vec![], vec![], vec![]);
+    let mut vertex = |(p, t, n): (usize, Option<usize>, Option<usize>)| -> usize {
+        let key = (p, t.filter(|_| has_uvs), n.filter(|_| has_normals));
+        *vertex_indices.entry(key).or_insert_with(|| {
+            let (x, y, z, _) = obj.positions[p];
+            positions.push(DVec3::new(x as f64, y as f64, z as f64));
+            if let Some(n) = key.2 {
+                let (x, y, z) = obj.normals[n];
+                normals.push(DVec3::new(x as f64, y as f64, z as f64));
+            }
+            if let Some(t) = key.1 {
+                let (u, v, _) = obj.tex_coords[t];
+                uvs.push(DVec2::new(u as f64, v as f64));
+            }
+            positions.len() - 1
+        })
+    };
+
+    let (materials, polygon_materials) = match mtl_dir {
+        Some(dir) => import_materials(&obj, dir),
+        None => (vec![], vec![None; polygons.len()]),
+    };
+
+    let mut indices = vec![];
+    let mut face_materials = vec![];
+    for (polygon, material) in polygons.into_iter().zip(polygon_materials) {
+        for i in 1..polygon.len().saturating_sub(1) {
+            indices.push([vertex(polygon[0]), vertex(polygon[i]), vertex(polygon[i + 1])]);
+            face_materials.push(material);
+        }
+    }
+    let mesh = TriangleMesh::new(positions, normals, uvs, indices, material)?;
+    if materials.is_empty() { Ok(mesh) } else { mesh.with_face_materials(materials, face_materials) }
+}
+
+/// Materials of the libraries `obj` refers to, and the index of the material of each polygon.
+/// Libraries or materials that cannot be found are reported and left out.
+fn import_materials(obj: &obj::raw::RawObj, dir: &Path) -> (Vec<Arc<dyn Material>>, Vec<Option<usize>>) {
+    let mut library = HashMap::new();
+    for name in &obj.material_libraries {
+        let path = dir.join(name);
+        let mtl = File::open(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|f| Ok(parse_mtl(BufReader::new(f))?));
+        match mtl {
+            Ok(mtl) => library.extend(mtl.materials),
+            Err(e) => eprintln!("warning: cannot read material library {}: {}", path.display(), e),
+        }
+    }
+
+    let mut materials = vec![];
+    let mut polygon_materials = vec![None; obj.polygons.len()];
+    // faces before any usemtl are grouped under an empty name and keep the default material
+    for (name, group) in obj.meshes.iter().filter(|(name, _)| !name.is_empty()).sorted_by_key(|(name, _)| *name) {
+        let Some(mtl) = library.get(name) else {
+            eprintln!("warning: material {} not found in the material libraries", name);
+            continue;
+        };
+        materials.push(mtl_to_material(mtl, dir));
+        for range in &group.polygons {
+            polygon_materials[range.start..range.end].fill(Some(materials.len() - 1));
+        }
+    }
+    (materials, polygon_materials)
+}
+
+fn mtl_color(color: &Option<MtlColor>) -> Option<DVec3> {
+    match color {
+        Some(MtlColor::Rgb(r, g, b)) => Some(DVec3::new(*r as f64, *g as f64, *b as f64)),
+        _ => None,
+    }
+}
+
+/// Pick the closest of our materials: emissive surfaces become `Light`, transparent ones
+/// `Dielectric`, ones more specular than diffuse `Metal`, and the rest `Diffuse`.
+fn mtl_to_material(mtl: &MtlMaterial, dir: &Path) -> Arc<dyn Material> {
+    let diffuse = mtl_color(&mtl.diffuse).unwrap_or(DVec3::repeat(0.8));
+    let specular = mtl_color(&mtl.specular).unwrap_or(DVec3::zeros());
+    let emissive = mtl_color(&mtl.emissive).unwrap_or(DVec3::zeros());
+
+    if emissive.max() > 0.0 {
+        return Arc::new(Light::new(emissive / emissive.max(), emissive.max()));
+    }
+    if mtl.dissolve.is_some_and(|d| d < 1.0) {
+        return Arc::new(Dielectric::new(mtl.optical_density.unwrap_or(1.5) as f64));
+    }
+    if specular.max() > diffuse.max() {
+        // Phong exponent to roughness, as in Walter et al. 2007
+        let exponent = mtl.specular_exponent.unwrap_or(0.0) as f64;
+        let fuzziness = (2.0 / (exponent + 2.0)).sqrt().min(1.0);
+        return Arc::new(Metal::new(specular, fuzziness));
+    }
+    let mut material = Diffuse::new(diffuse);
+    if let Some(ref map) = mtl.diffuse_map {
+        match ImageTexture::load(dir.join(&map.file)) {
+            Ok(texture) => material.texture = Some(texture),
+            Err(e) => eprintln!("warning: {}", e),
+        }
+    }
+    Arc::new(material)
+}
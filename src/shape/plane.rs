@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use bvh::aabb::Bounded;
+use nalgebra_glm as glm;
+use serde::{Deserialize, Serialize};
+
+use crate::{hit::{HitRecord, Ray}, material::Material};
+use super::{Shape, plane_toi};
+
+/// Infinite plane through `point`. Its normal points to the front side.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Plane {
+    pub point: glm::DVec3,
+    pub normal: glm::DVec3,
+    pub material: Arc<dyn Material>,
+}
+
+impl Bounded for Plane {
+    /// An infinite plane has no finite bounding box. See [`Shape::is_bounded`].
+    fn aabb(&self) -> bvh::aabb::AABB {
+        bvh::aabb::AABB::with_bounds(bvh::Point3::splat(f32::MIN), bvh::Point3::splat(f32::MAX))
+    }
+}
+
+#[typetag::serde]
+impl Shape for Plane {
+    fn hit(&self, ray: &Ray) -> Option<HitRecord> {
+        let normal = self.normal.normalize();
+        let toi = plane_toi(ray, &self.point, &normal)?;
+        if toi < 0.0 {
+            return None;
+        }
+        let point = ray.origin + toi * ray.direction;
+        Some(HitRecord::new(toi, point, normal))
+    }
+
+    fn material(&self, _hit: &HitRecord) -> Arc<dyn Material> {
+        self.material.clone()
+    }
+
+    fn is_bounded(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,298 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, ensure};
+use nalgebra_glm::{DVec2, DVec3};
+
+use crate::material::Material;
+use super::TriangleMesh;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+#[derive(Clone, Copy)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> anyhow::Result<Self> {
+        Ok(match name {
+            "char" | "int8" => ScalarType::I8,
+            "uchar" | "uint8" => ScalarType::U8,
+            "short" | "int16" => ScalarType::I16,
+            "ushort" | "uint16" => ScalarType::U16,
+            "int" | "int32" => ScalarType::I32,
+            "uint" | "uint32" => ScalarType::U32,
+            "float" | "float32" => ScalarType::F32,
+            "double" | "float64" => ScalarType::F64,
+            _ => bail!("unknown PLY property type {}", name),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+
+    /// Largest value of integer types, by which colors are normalized.
+    fn max_value(self) -> f64 {
+        match self {
+            ScalarType::I8 => i8::MAX as f64,
+            ScalarType::U8 => u8::MAX as f64,
+            ScalarType::I16 => i16::MAX as f64,
+            ScalarType::U16 => u16::MAX as f64,
+            ScalarType::I32 => i32::MAX as f64,
+            ScalarType::U32 => u32::MAX as f64,
+            ScalarType::F32 | ScalarType::F64 => 1.0,
+        }
+    }
+}
+
+enum PropertyType {
+    Scalar(ScalarType),
+    List { count: ScalarType, item: ScalarType },
+}
+
+struct Property {
+    name: String,
+    ty: PropertyType,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Reads the values following the header, whatever the format.
+struct Body<'a> {
+    format: Format,
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Body<'_> {
+    fn read(&mut self, ty: ScalarType) -> anyhow::Result<f64> {
+        match self.format {
+            Format::Ascii => {
+                let rest = &self.bytes[self.position..];
+                let start = rest.iter().position(|b| !b.is_ascii_whitespace()).ok_or_else(|| anyhow!("unexpected end of PLY data"))?;
+                let len = rest[start..].iter().position(|b| b.is_ascii_whitespace()).unwrap_or(rest.len() - start);
+                self.position += start + len;
+                Ok(std::str::from_utf8(&rest[start..start + len])?.parse::<f64>()?)
+            }
+            Format::BinaryLittleEndian => {
+                let size = ty.size();
+                ensure!(self.position + size <= self.bytes.len(), "unexpected end of PLY data");
+                let b = &self.bytes[self.position..self.position + size];
+                self.position += size;
+                Ok(match ty {
+                    ScalarType::I8 => b[0] as i8 as f64,
+                    ScalarType::U8 => b[0] as f64,
+                    ScalarType::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+                    ScalarType::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+                    ScalarType::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    ScalarType::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    ScalarType::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    ScalarType::F64 => f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]),
+                })
+            }
+        }
+    }
+
+    /// Values of all properties of one element; lists are flattened after their count.
+    fn read_element(&mut self, element: &Element) -> anyhow::Result<Vec<Vec<f64>>> {
+        element.properties.iter().map(|p| match p.ty {
+            PropertyType::Scalar(ty) => Ok(vec![self.read(ty)?]),
+            PropertyType::List { count, item } => {
+                let n = self.read(count)? as usize;
+                (0..n).map(|_| self.read(item)).collect()
+            }
+        }).collect()
+    }
+}
+
+/// Parse an ASCII or binary little-endian PLY file into a mesh. Vertex normals, colors
+/// and texture coordinates are kept when present, and polygons are split into triangle fans.
+pub fn load_ply(buffer: &[u8], material: Arc<dyn Material>) -> anyhow::Result<TriangleMesh> {
+    const END_HEADER: &[u8] = b"end_header";
+    let header_len = buffer.windows(END_HEADER.len()).position(|w| w == END_HEADER)
+        .ok_or_else(|| anyhow!("PLY header has no end_header"))?;
+    let body_start = buffer[header_len..].iter().position(|&b| b == b'\n')
+        .map(|i| header_len + i + 1)
+        .unwrap_or(buffer.len());
+
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    for line in std::str::from_utf8(&buffer[..header_len])?.lines() {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            ["ply"] | [] => {}
+            ["comment" | "obj_info", ..] => {}
+            ["format", "ascii", _] => format = Some(Format::Ascii),
+            ["format", "binary_little_endian", _] => format = Some(Format::BinaryLittleEndian),
+            ["format", other, _] => bail!("unsupported PLY format {}", other),
+            ["element", name, count] => elements.push(Element { name: name.to_string(), count: count.parse()?, properties: vec![] }),
+            ["property", "list", count, item, name] => {
+                let element = elements.last_mut().ok_or_else(|| anyhow!("PLY property outside an element"))?;
+                let ty = PropertyType::List { count: ScalarType::parse(count)?, item: ScalarType::parse(item)? };
+                element.properties.push(Property { name: name.to_string(), ty });
+            }
+            ["property", ty, name] => {
+                let element = elements.last_mut().ok_or_else(|| anyhow!("PLY property outside an element"))?;
+                element.properties.push(Property { name: name.to_string(), ty: PropertyType::Scalar(ScalarType::parse(ty)?) });
+            }
+            _ => bail!("unexpected PLY header line: {}", line),
+        }
+    }
+    let format = format.ok_or_else(|| anyhow!("PLY header has no format"))?;
+
+    let mut body = Body { format, bytes: &buffer[body_start..], position: 0 };
+    let (mut positions, mut normals, mut colors, mut uvs) = (vec![], vec![], vec![], vec![]);
+    let mut indices = vec![];
+    for element in &elements {
+        let find = |names: &[&str]| element.properties.iter().position(|p| names.contains(&p.name.as_str()));
+        match element.name.as_str() {
+            "vertex" => {
+                let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+                let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+                let color = [find(&["red", "r"]), find(&["green", "g"]), find(&["blue", "b"])];
+                let uv = [find(&["u", "s", "texture_u"]), find(&["v", "t", "texture_v"])];
+                let [Some(x), Some(y), Some(z)] = position else {
+                    bail!("PLY vertices have no x, y and z");
+                };
+                let color_scale = match color[0].map(|i| &element.properties[i].ty) {
+                    Some(PropertyType::Scalar(ty)) => ty.max_value(),
+                    _ => 1.0,
+                };
+                for _ in 0..element.count {
+                    let values = body.read_element(element)?;
+                    let get = |i: usize| values[i].first().copied().unwrap_or(0.0);
+                    positions.push(DVec3::new(get(x), get(y), get(z)));
+                    if let [Some(nx), Some(ny), Some(nz)] = normal {
+                        normals.push(DVec3::new(get(nx), get(ny), get(nz)));
+                    }
+                    if let [Some(r), Some(g), Some(b)] = color {
+                        colors.push(DVec3::new(get(r), get(g), get(b)) / color_scale);
+                    }
+                    if let [Some(u), Some(v)] = uv {
+                        uvs.push(DVec2::new(get(u), get(v)));
+                    }
+                }
+            }
+            "face" => {
+                let vertices = find(&["vertex_indices", "vertex_index"])
+                    .ok_or_else(|| anyhow!("PLY faces have no vertex_indices"))?;
+                for _ in 0..element.count {
+                    let polygon = body.read_element(element)?.swap_remove(vertices);
+                    for i in 1..polygon.len().saturating_sub(1) {
+                        indices.push([polygon[0] as usize, polygon[i] as usize, polygon[i + 1] as usize]);
+                    }
+                }
+            }
+            _ => {
+                for _ in 0..element.count {
+                    body.read_element(element)?;
+                }
+            }
+        }
+    }
+    ensure!(indices.iter().flatten().all(|&i| i < positions.len()), "PLY face refers to a missing vertex");
+
+    TriangleMesh::new(positions, normals, uvs, indices, material)?.with_colors(colors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Diffuse;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Diffuse { color_diffuse: DVec3::repeat(0.5), texture: None })
+    }
+
+    #[test]
+    fn ascii_quad_with_colors_is_split_into_a_fan() {
+        let ply = b"ply
+format ascii 1.0
+comment a unit square
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3
+";
+        let mesh = load_ply(ply, material()).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.colors[1], DVec3::new(0.0, 1.0, 0.0));
+        assert!(mesh.normals.is_empty() && mesh.uvs.is_empty());
+    }
+
+    #[test]
+    fn binary_triangle_skips_unknown_elements() {
+        let mut ply = b"ply
+format binary_little_endian 1.0
+element vertex 3
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+element edge 1
+property int vertex1
+property int vertex2
+end_header
+".to_vec();
+        for v in [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 2.0, 0.0]] {
+            v.iter().for_each(|c| ply.extend(c.to_le_bytes()));
+        }
+        ply.push(3);
+        [0i32, 1, 2, 0, 1].iter().for_each(|i| ply.extend(i.to_le_bytes()));
+
+        let mesh = load_ply(&ply, material()).unwrap();
+        assert_eq!(mesh.positions[2], DVec3::new(0.0, 2.0, 0.0));
+        assert_eq!(mesh.indices, vec![[0, 1, 2]]);
+    }
+
+    #[test]
+    fn faces_referring_to_missing_vertices_are_rejected() {
+        let ply = b"ply
+format ascii 1.0
+element vertex 1
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0
+3 0 1 2
+";
+        assert!(load_ply(ply, material()).is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use std::sync::Arc;
+
+use bvh::aabb::Bounded;
+use nalgebra_glm as glm;
+use serde::{Deserialize, Serialize};
+
+use crate::{hit::{HitRecord, Ray}, material::Material};
+use super::{Shape, aabb_from, plane_toi};
+
+/// Parallelogram spanned by `u` and `v` from corner `origin`. Its normal is u × v.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Quad {
+    pub origin: glm::DVec3,
+    pub u: glm::DVec3,
+    pub v: glm::DVec3,
+    pub material: Arc<dyn Material>,
+}
+
+impl Bounded for Quad {
+    fn aabb(&self) -> bvh::aabb::AABB {
+        let corners = [self.origin, self.origin + self.u, self.origin + self.v, self.origin + self.u + self.v];
+        let min = corners.iter().fold(corners[0], |m, p| glm::min2(&m, p));
+        let max = corners.iter().fold(corners[0], |m, p| glm::max2(&m, p));
+        aabb_from(&min, &max)
+    }
+}
+
+#[typetag::serde]
+impl Shape for Quad {
+    fn hit(&self, ray: &Ray) -> Option<HitRecord> {
+        let n = self.u.cross(&self.v);
+        let normal = n.normalize();
+        let toi = plane_toi(ray, &self.origin, &normal)?;
+        if toi < 0.0 {
+            return None;
+        }
+        let point = ray.origin + toi * ray.direction;
+
+        // planar coordinates of the hit point: point = origin + a * u + b * v
+        let q = point - self.origin;
+        let w = n / n.norm_squared();
+        let a = w.dot(&q.cross(&self.v));
+        let b = w.dot(&self.u.cross(&q));
+        if !(0.0..=1.0).contains(&a) || !(0.0..=1.0).contains(&b) {
+            return None;
+        }
+        Some(HitRecord::new(toi, point, normal))
+    }
+
+    fn material(&self, _hit: &HitRecord) -> Arc<dyn Material> {
+        self.material.clone()
+    }
+}
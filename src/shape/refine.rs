@@ -0,0 +1,211 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::ensure;
+use nalgebra_glm::{DVec2, DVec3};
+
+use crate::material::ImageTexture;
+use super::TriangleMesh;
+
+/// Vertex attributes and faces of a mesh while it is refined.
+struct Parts {
+    positions: Vec<DVec3>,
+    uvs: Vec<DVec2>,
+    colors: Vec<DVec3>,
+    indices: Vec<[usize; 3]>,
+    face_materials: Vec<Option<usize>>,
+}
+
+/// Index of each vertex among the distinct positions, and their count. Vertices split
+/// only by their UVs or normals, as at texture seams, share one position.
+fn weld(positions: &[DVec3]) -> (Vec<usize>, usize) {
+    let mut ids = HashMap::new();
+    let welded = positions.iter().map(|p| {
+        let count = ids.len();
+        *ids.entry(p.map(f64::to_bits)).or_insert(count)
+    }).collect();
+    (welded, ids.len())
+}
+
+/// Smooth normals weighted by face area, the same for all vertices at one position.
+fn vertex_normals(parts: &Parts) -> Vec<DVec3> {
+    let (welded, count) = weld(&parts.positions);
+    let mut normals = vec![DVec3::zeros(); count];
+    for face in &parts.indices {
+        let [a, b, c] = face.map(|i| parts.positions[i]);
+        let normal = (b - a).cross(&(c - a));
+        for &i in face {
+            normals[welded[i]] += normal;
+        }
+    }
+    welded.iter().map(|&w| normals[w].try_normalize(0.0).unwrap_or(DVec3::y())).collect()
+}
+
+/// One level of Loop subdivision: every triangle is split in four, and vertices are moved
+/// towards their neighbors. Boundaries follow the curve rules, so open meshes keep their outline.
+/// UVs and colors of new vertices are interpolated linearly.
+fn loop_subdivide(parts: Parts) -> Parts {
+    let (welded, count) = weld(&parts.positions);
+    let mut points = vec![DVec3::zeros(); count];
+    for (i, &w) in welded.iter().enumerate() {
+        points[w] = parts.positions[i];
+    }
+
+    // corners opposite of each edge, by the positions of its ends
+    let mut edges: BTreeMap<(usize, usize), Vec<usize>> = BTreeMap::new();
+    for face in &parts.indices {
+        let w = face.map(|i| welded[i]);
+        for k in 0..3 {
+            let (a, b) = (w[k], w[(k + 1) % 3]);
+            edges.entry((a.min(b), a.max(b))).or_default().push(w[(k + 2) % 3]);
+        }
+    }
+    let mut neighbors = vec![vec![]; count];
+    let mut boundary_neighbors = vec![vec![]; count];
+    for (&(a, b), opposite) in &edges {
+        neighbors[a].push(b);
+        neighbors[b].push(a);
+        if opposite.len() != 2 {
+            boundary_neighbors[a].push(b);
+            boundary_neighbors[b].push(a);
+        }
+    }
+
+    let moved = (0..count).map(|p| match boundary_neighbors[p].as_slice() {
+        [] => {
+            let n = neighbors[p].len() as f64;
+            let beta = if neighbors[p].len() == 3 { 3.0 / 16.0 } else { 3.0 / (8.0 * n) };
+            (1.0 - n * beta) * points[p] + beta * neighbors[p].iter().map(|&q| points[q]).sum::<DVec3>()
+        }
+        [a, b] => 0.75 * points[p] + 0.125 * (points[*a] + points[*b]),
+        // corners of non-manifold edges stay where they are
+        _ => points[p],
+    }).collect::<Vec<_>>();
+    let edge_point = |a: usize, b: usize| match edges[&(a.min(b), a.max(b))].as_slice() {
+        [c, d] => 0.375 * (points[a] + points[b]) + 0.125 * (points[*c] + points[*d]),
+        _ => (points[a] + points[b]) / 2.0,
+    };
+
+    let mut refined = Parts {
+        positions: welded.iter().map(|&w| moved[w]).collect(),
+        uvs: parts.uvs.clone(),
+        colors: parts.colors.clone(),
+        indices: Vec::with_capacity(4 * parts.indices.len()),
+        face_materials: parts.face_materials.iter().flat_map(|&m| [m; 4]).collect(),
+    };
+    // new vertices by the vertices of their edge, so that seams keep their split attributes
+    let mut midpoints = HashMap::new();
+    let mut midpoint = |refined: &mut Parts, a: usize, b: usize| *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+        refined.positions.push(edge_point(welded[a], welded[b]));
+        if !parts.uvs.is_empty() {
+            refined.uvs.push((parts.uvs[a] + parts.uvs[b]) / 2.0);
+        }
+        if !parts.colors.is_empty() {
+            refined.colors.push((parts.colors[a] + parts.colors[b]) / 2.0);
+        }
+        refined.positions.len() - 1
+    });
+    for &[a, b, c] in &parts.indices {
+        let (ab, bc, ca) = (midpoint(&mut refined, a, b), midpoint(&mut refined, b, c), midpoint(&mut refined, c, a));
+        refined.indices.extend([[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]);
+    }
+    refined
+}
+
+/// Move every vertex along its normal by `scale` times the filtered brightness of `texture` at its UV.
+/// Vertices at one position move by their average, so that seams do not open.
+fn displace(parts: &mut Parts, texture: &ImageTexture, scale: f64) -> anyhow::Result<()> {
+    ensure!(!parts.uvs.is_empty(), "displacement needs a mesh with texture coordinates");
+    let normals = vertex_normals(parts);
+    let (welded, count) = weld(&parts.positions);
+    let mut offsets = vec![(0.0, 0); count];
+    for (i, &w) in welded.iter().enumerate() {
+        offsets[w].0 += texture.sample_bilinear(&parts.uvs[i]).mean();
+        offsets[w].1 += 1;
+    }
+    for (i, &w) in welded.iter().enumerate() {
+        let (sum, n) = offsets[w];
+        parts.positions[i] += normals[i] * scale * sum / n as f64;
+    }
+    Ok(())
+}
+
+/// Apply `subdivision` levels of Loop subdivision and then `displacement` (texture and scale)
+/// to a mesh, which is smooth shaded afterwards.
+pub fn refine_mesh(mesh: TriangleMesh, subdivision: u32, displacement: Option<(&ImageTexture, f64)>) -> anyhow::Result<TriangleMesh> {
+    if subdivision == 0 && displacement.is_none() {
+        return Ok(mesh);
+    }
+    let TriangleMesh { positions, uvs, colors, indices, material, materials, face_materials, .. } = mesh;
+    let mut parts = Parts { positions, uvs, colors, indices, face_materials };
+    for _ in 0..subdivision {
+        parts = loop_subdivide(parts);
+    }
+    if let Some((texture, scale)) = displacement {
+        displace(&mut parts, texture, scale)?;
+    }
+    let normals = vertex_normals(&parts);
+    TriangleMesh::new(parts.positions, normals, parts.uvs, parts.indices, material)?
+        .with_colors(parts.colors)?
+        .with_face_materials(materials, parts.face_materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(positions: Vec<DVec3>, uvs: Vec<DVec2>, indices: Vec<[usize; 3]>) -> Parts {
+        let face_materials = vec![None; indices.len()];
+        Parts { positions, uvs, colors: vec![], indices, face_materials }
+    }
+
+    fn assert_close(a: &DVec3, b: &DVec3) {
+        assert!((a - b).norm() < 1e-9, "{:?} is not {:?}", a, b);
+    }
+
+    #[test]
+    fn tetrahedron_shrinks_towards_its_center() {
+        let corners = vec![DVec3::new(1.0, 1.0, 1.0), DVec3::new(1.0, -1.0, -1.0), DVec3::new(-1.0, 1.0, -1.0), DVec3::new(-1.0, -1.0, 1.0)];
+        let tetrahedron = parts(corners.clone(), vec![], vec![[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]]);
+
+        let once = loop_subdivide(tetrahedron);
+        assert_eq!(once.indices.len(), 16);
+        assert_eq!(once.positions.len(), 10);
+        assert_eq!(once.face_materials.len(), 16);
+        // the neighbors of each corner sum to minus the corner, as do the far corners of each edge
+        for (moved, corner) in once.positions.iter().zip(&corners) {
+            assert_close(moved, &(corner / 4.0));
+        }
+        assert_close(&once.positions[4], &((corners[0] + corners[1]) / 4.0));
+
+        let twice = loop_subdivide(once);
+        assert_eq!(twice.indices.len(), 64);
+        assert_eq!(twice.positions.len(), 34);
+    }
+
+    #[test]
+    fn open_triangle_keeps_its_outline() {
+        let triangle = parts(
+            vec![DVec3::zeros(), DVec3::x(), DVec3::y()],
+            vec![DVec2::zeros(), DVec2::x(), DVec2::y()],
+            vec![[0, 1, 2]],
+        );
+        let refined = loop_subdivide(triangle);
+        assert_eq!(refined.indices.len(), 4);
+        assert_close(&refined.positions[0], &DVec3::new(0.125, 0.125, 0.0));
+        assert_close(&refined.positions[3], &DVec3::new(0.5, 0.0, 0.0));
+        assert_eq!(refined.uvs[3], DVec2::new(0.5, 0.0));
+        assert!(refined.positions.iter().all(|p| p.z == 0.0));
+    }
+
+    #[test]
+    fn vertices_split_at_seams_move_together() {
+        // a square of two triangles whose shared edge has its own UVs in each one
+        let positions = vec![DVec3::zeros(), DVec3::x(), DVec3::new(1.0, 1.0, 0.0), DVec3::zeros(), DVec3::new(1.0, 1.0, 0.0), DVec3::y()];
+        let uvs = positions.iter().enumerate().map(|(i, p)| DVec2::new(p.x, p.y + if i < 3 { 0.0 } else { 1.0 })).collect();
+        let refined = loop_subdivide(parts(positions, uvs, vec![[0, 1, 2], [3, 4, 5]]));
+        assert_eq!(refined.indices.len(), 8);
+        assert_close(&refined.positions[0], &refined.positions[3]);
+        assert_close(&refined.positions[2], &refined.positions[4]);
+        assert_ne!(refined.uvs[0], refined.uvs[3]);
+    }
+}
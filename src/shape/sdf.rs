@@ -0,0 +1,200 @@
+use std::sync::Arc;
+
+use bvh::aabb::Bounded;
+use nalgebra_glm::DVec3;
+use serde::{Deserialize, Serialize};
+
+use crate::{hit::{HitRecord, Ray}, material::Material};
+use super::{Shape, aabb_from};
+
+const MAX_STEPS: usize = 512;
+
+/// A signed distance field: negative inside, positive outside and zero on the surface.
+/// Distances may underestimate but never overestimate, or sphere tracing steps through the surface.
+#[typetag::serde(tag = "type")]
+pub trait Sdf: Send + Sync {
+    fn distance(&self, point: &DVec3) -> f64;
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SdfSphere {
+    pub center: DVec3,
+    pub radius: f64,
+}
+
+#[typetag::serde(name = "Sphere")]
+impl Sdf for SdfSphere {
+    fn distance(&self, point: &DVec3) -> f64 {
+        (point - self.center).norm() - self.radius
+    }
+}
+
+/// Axis aligned box with edges rounded off by `radius`, which may be 0.
+/// The rounding stays within `half_size`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SdfBox {
+    pub center: DVec3,
+    pub half_size: DVec3,
+    #[serde(default)]
+    pub radius: f64,
+}
+
+#[typetag::serde(name = "Box")]
+impl Sdf for SdfBox {
+    fn distance(&self, point: &DVec3) -> f64 {
+        let q = (point - self.center).abs() - self.half_size.add_scalar(-self.radius);
+        q.sup(&DVec3::zeros()).norm() + q.max().min(0.0) - self.radius
+    }
+}
+
+/// Segment from `a` to `b` thickened by `radius`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SdfCapsule {
+    pub a: DVec3,
+    pub b: DVec3,
+    pub radius: f64,
+}
+
+#[typetag::serde(name = "Capsule")]
+impl Sdf for SdfCapsule {
+    fn distance(&self, point: &DVec3) -> f64 {
+        let (pa, ba) = (point - self.a, self.b - self.a);
+        let h = if ba.norm_squared() > 0.0 { (pa.dot(&ba) / ba.norm_squared()).clamp(0.0, 1.0) } else { 0.0 };
+        (pa - h * ba).norm() - self.radius
+    }
+}
+
+/// Union of two fields, blended over a distance of about `k`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SmoothUnion {
+    pub left: Arc<dyn Sdf>,
+    pub right: Arc<dyn Sdf>,
+    pub k: f64,
+}
+
+#[typetag::serde]
+impl Sdf for SmoothUnion {
+    fn distance(&self, point: &DVec3) -> f64 {
+        smooth_min(self.left.distance(point), self.right.distance(point), self.k)
+    }
+}
+
+/// `left` with `right` carved out of it, the edge blended over a distance of about `k`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SmoothSubtraction {
+    pub left: Arc<dyn Sdf>,
+    pub right: Arc<dyn Sdf>,
+    pub k: f64,
+}
+
+#[typetag::serde]
+impl Sdf for SmoothSubtraction {
+    fn distance(&self, point: &DVec3) -> f64 {
+        -smooth_min(-self.left.distance(point), self.right.distance(point), self.k)
+    }
+}
+
+/// Polynomial smooth minimum; the plain minimum when `k` is 0.
+fn smooth_min(a: f64, b: f64, k: f64) -> f64 {
+    if k <= 0.0 {
+        return a.min(b);
+    }
+    let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+    b + (a - b) * h - k * h * (1.0 - h)
+}
+
+/// The surface of a signed distance field, found by sphere tracing within the box from `min` to `max`.
+/// Nothing of the field outside the box is drawn.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SdfShape {
+    pub sdf: Arc<dyn Sdf>,
+    pub min: DVec3,
+    pub max: DVec3,
+    pub material: Arc<dyn Material>,
+}
+
+impl SdfShape {
+    /// Distance under which a point counts as on the surface, relative to the size of the box.
+    fn epsilon(&self) -> f64 {
+        1e-5 * (self.max - self.min).norm()
+    }
+
+    /// Toi range of the ray within the bounding box.
+    fn box_range(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let (mut t_near, mut t_far) = (f64::NEG_INFINITY, f64::INFINITY);
+        for axis in 0..3 {
+            if ray.direction[axis] == 0.0 {
+                if ray.origin[axis] < self.min[axis] || ray.origin[axis] > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (self.min[axis] - ray.origin[axis]) / ray.direction[axis];
+            let t1 = (self.max[axis] - ray.origin[axis]) / ray.direction[axis];
+            t_near = t_near.max(t0.min(t1));
+            t_far = t_far.min(t0.max(t1));
+        }
+        (t_near <= t_far).then_some((t_near, t_far))
+    }
+
+    /// Outward normal as the central difference gradient of the field.
+    fn normal(&self, point: &DVec3, ray: &Ray) -> DVec3 {
+        let h = self.epsilon();
+        let mut gradient = DVec3::zeros();
+        for axis in 0..3 {
+            let mut offset = DVec3::zeros();
+            offset[axis] = h;
+            gradient[axis] = self.sdf.distance(&(point + offset)) - self.sdf.distance(&(point - offset));
+        }
+        gradient.try_normalize(0.0).unwrap_or(-ray.direction.normalize())
+    }
+}
+
+impl Bounded for SdfShape {
+    fn aabb(&self) -> bvh::aabb::AABB {
+        aabb_from(&self.min, &self.max)
+    }
+}
+
+#[typetag::serde]
+impl Shape for SdfShape {
+    fn hit(&self, ray: &Ray) -> Option<HitRecord> {
+        self.hit_with_bound(ray, (0.0, f64::INFINITY))
+    }
+
+    fn hit_with_bound(&self, ray: &Ray, bound: (f64, f64)) -> Option<HitRecord> {
+        let (t_near, t_far) = self.box_range(ray)?;
+        let (start, end) = (t_near.max(bound.0), t_far.min(bound.1));
+        if start > end {
+            return None;
+        }
+        let epsilon = self.epsilon();
+        let speed = ray.direction.norm();
+        let position = |toi: f64| ray.origin + toi * ray.direction;
+
+        // Rays scattered off the surface start on it, so wait until they have left it.
+        // Marching on absolute distances also finds the way out from the inside.
+        let mut leaving = self.sdf.distance(&position(start)).abs() < epsilon;
+        let mut toi = start;
+        for _ in 0..MAX_STEPS {
+            if toi > end {
+                return None;
+            }
+            let point = position(toi);
+            let distance = self.sdf.distance(&point).abs();
+            if distance < epsilon {
+                if !leaving {
+                    return Some(HitRecord::new(toi, point, self.normal(&point, ray)));
+                }
+            } else {
+                leaving = false;
+            }
+            toi += distance.max(epsilon) / speed;
+        }
+        None
+    }
+
+    fn material(&self, _hit: &HitRecord) -> Arc<dyn Material> {
+        self.material.clone()
+    }
+}
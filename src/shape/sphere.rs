@@ -2,26 +2,39 @@ use std::sync::Arc;
 
 use bvh::{aabb::Bounded};
 
+use itertools::Itertools;
 use nalgebra_glm as glm;
 use serde::{Deserialize, Serialize};
-use crate::{hit::HitRecord, material::Material};
-use super::Shape;
+use crate::{hit::{HitRecord, Ray}, material::Material, utils::solve_quadratic};
+use super::{Shape, position_at, aabb_from, pair_up};
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Sphere {
     pub center: glm::DVec3,
     pub radius: f64,
-    pub material: Arc<dyn Material>
+    pub material: Arc<dyn Material>,
+    /// Center at time 1. The sphere is static if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub center_end: Option<glm::DVec3>,
+}
+
+impl Sphere {
+    pub fn new(center: glm::DVec3, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self { center, radius, material, center_end: None }
+    }
+
+    pub fn center_at(&self, time: f64) -> glm::DVec3 {
+        position_at(&self.center, &self.center_end, time)
+    }
 }
 
 impl Bounded for Sphere {
     fn aabb(&self) -> bvh::aabb::AABB {
         let quad_cube = glm::vec3(self.radius, self.radius, self.radius);
-        let min = self.center - quad_cube;
-        let max = self.center + quad_cube;
-        bvh::aabb::AABB {
-            min: bvh::Vector3::new(min.x as f32, min.y as f32, min.z as f32),
-            max: bvh::Vector3::new(max.x as f32, max.y as f32, max.z as f32),
+        let aabb_at = |center: &glm::DVec3| aabb_from(&(center - quad_cube), &(center + quad_cube));
+        match self.center_end {
+            Some(ref center_end) => aabb_at(&self.center).join(&aabb_at(center_end)),
+            None => aabb_at(&self.center),
         }
     }
 }
@@ -36,8 +49,9 @@ impl Shape for Sphere {
         */
         let origin = ray.origin;
         let ray_dir = ray.direction;
+        let center = self.center_at(ray.time);
 
-        let oc = origin - self.center;
+        let oc = origin - center;
 
         let a = ray_dir.norm_squared();
         let b = 2.0 * ray_dir.dot(&oc);
@@ -59,11 +73,7 @@ impl Shape for Sphere {
                     if t2 >= 0.0 { t2 } else { t1 }
                 };
                 let point = origin + toi * ray_dir;
-                Some(HitRecord {
-                    toi,
-                    point,
-                    normal: (point - self.center).normalize(),
-                })
+                Some(HitRecord::new(toi, point, (point - center).normalize()))
             }
         }
     }
@@ -71,4 +81,44 @@ impl Shape for Sphere {
     fn material(&self, _hit: &HitRecord) -> Arc<dyn Material> {
         self.material.clone()
     }
+
+    fn intervals(&self, ray: &Ray) -> Option<Vec<(HitRecord, HitRecord)>> {
+        let center = self.center_at(ray.time);
+        let oc = ray.origin - center;
+        let hits = solve_quadratic([
+            oc.norm_squared() - self.radius * self.radius,
+            2.0 * ray.direction.dot(&oc),
+            ray.direction.norm_squared(),
+        ])
+        .into_iter()
+        .sorted_by(|a, b| a.partial_cmp(b).unwrap())
+        .map(|toi| {
+            let point = ray.origin + toi * ray.direction;
+            HitRecord::new(toi, point, (point - center).normalize())
+        })
+        .collect_vec();
+        // a tangent ray touches the sphere without entering it
+        Some(if hits.len() == 2 { pair_up(hits) } else { vec![] })
+    }
+
+    fn is_closed(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Diffuse;
+
+    #[test]
+    fn small_spheres_are_entered_and_left() {
+        let sphere = Sphere::new(glm::DVec3::zeros(), 1e-5, Arc::new(Diffuse::new(glm::DVec3::repeat(0.5))));
+        let ray = Ray::new(glm::DVec3::new(-1e-3, 0.0, 0.0), glm::DVec3::new(1.0, 0.0, 0.0));
+        let intervals = sphere.intervals(&ray).unwrap();
+        assert_eq!(intervals.len(), 1);
+        let (entry, exit) = &intervals[0];
+        assert!((entry.toi - (1e-3 - 1e-5)).abs() < 1e-12);
+        assert!((exit.toi - (1e-3 + 1e-5)).abs() < 1e-12);
+    }
 }
@@ -0,0 +1,113 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{anyhow, ensure};
+use nalgebra_glm::DVec3;
+
+use crate::material::Material;
+use super::TriangleMesh;
+
+/// Parse an ASCII or binary STL file into a flat shaded mesh.
+/// Facet normals in the file are ignored in favor of the vertex order.
+pub fn load_stl(buffer: &[u8], material: Arc<dyn Material>) -> anyhow::Result<TriangleMesh> {
+    let triangles = if is_binary(buffer) { binary_triangles(buffer) } else { ascii_triangles(buffer)? };
+
+    // STL repeats shared vertices in every facet
+    let mut vertex_indices = HashMap::new();
+    let mut positions = vec![];
+    let indices = triangles.iter().map(|triangle| triangle.map(|p| {
+        *vertex_indices.entry(p.map(f64::to_bits)).or_insert_with(|| {
+            positions.push(DVec3::new(p[0], p[1], p[2]));
+            positions.len() - 1
+        })
+    })).collect();
+
+    TriangleMesh::new(positions, vec![], vec![], indices, material)
+}
+
+/// Binary files may also start with "solid", so go by whether the size matches the triangle count.
+fn is_binary(buffer: &[u8]) -> bool {
+    if buffer.len() < 84 {
+        return false;
+    }
+    let count = u32::from_le_bytes([buffer[80], buffer[81], buffer[82], buffer[83]]) as usize;
+    buffer.len() == 84 + 50 * count
+}
+
+fn binary_triangles(buffer: &[u8]) -> Vec<[[f64; 3]; 3]> {
+    // each record is a normal, three vertices and a two byte attribute
+    buffer[84..].chunks_exact(50).map(|record| {
+        let read_f32 = |offset: usize| f32::from_le_bytes(record[offset..offset + 4].try_into().unwrap()) as f64;
+        [1, 2, 3].map(|v| [read_f32(12 * v), read_f32(12 * v + 4), read_f32(12 * v + 8)])
+    }).collect()
+}
+
+fn ascii_triangles(buffer: &[u8]) -> anyhow::Result<Vec<[[f64; 3]; 3]>> {
+    let text = std::str::from_utf8(buffer)?;
+    ensure!(text.trim_start().starts_with("solid"), "not an STL file");
+    let mut vertices = vec![];
+    let mut words = text.split_whitespace();
+    while let Some(word) = words.next() {
+        if word == "vertex" {
+            let mut coordinate = || -> anyhow::Result<f64> {
+                Ok(words.next().ok_or_else(|| anyhow!("unexpected end of STL data"))?.parse::<f64>()?)
+            };
+            vertices.push([coordinate()?, coordinate()?, coordinate()?]);
+        }
+    }
+    ensure!(vertices.len() % 3 == 0, "STL facets must have three vertices");
+    Ok(vertices.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Diffuse;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Diffuse { color_diffuse: DVec3::repeat(0.5), texture: None })
+    }
+
+    #[test]
+    fn ascii_facets_share_vertices() {
+        let stl = b"solid square
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 0 0
+    vertex 1 1 0
+  endloop
+endfacet
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 1 0
+    vertex 0 1 0
+  endloop
+endfacet
+endsolid square
+";
+        let mesh = load_stl(stl, material()).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn binary_file_starting_with_solid_is_read_as_binary() {
+        let mut stl = b"solid but actually binary".to_vec();
+        stl.resize(80, 0);
+        stl.extend(1u32.to_le_bytes());
+        for v in [[0.0f32, 0.0, 1.0], [0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]] {
+            v.iter().for_each(|c| stl.extend(c.to_le_bytes()));
+        }
+        stl.extend([0, 0]);
+
+        let mesh = load_stl(&stl, material()).unwrap();
+        assert_eq!(mesh.positions, vec![DVec3::zeros(), DVec3::new(1.0, 0.0, 0.0), DVec3::new(0.0, 1.0, 0.0)]);
+        assert_eq!(mesh.indices, vec![[0, 1, 2]]);
+    }
+
+    #[test]
+    fn ascii_facet_with_missing_vertex_is_rejected() {
+        assert!(load_stl(b"solid s facet outer loop vertex 0 0 0 vertex 1 0 0 endloop endfacet endsolid", material()).is_err());
+    }
+}
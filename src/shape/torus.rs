@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+use bvh::aabb::Bounded;
+use nalgebra_glm::{DMat3, DVec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{hit::{HitRecord, Ray}, material::Material, utils::{orthonormal_basis, solve_quartic, solve_quadratic}};
+use super::{Shape, aabb_from, disk_aabb, nearest_hit, pair_up};
+
+/// Torus around `center`, lying in the plane perpendicular to `axis`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Torus {
+    pub center: DVec3,
+    pub axis: DVec3,
+    /// Distance from the center to the middle of the tube.
+    pub major_radius: f64,
+    /// Radius of the tube.
+    pub minor_radius: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl Torus {
+    /// Rotation from torus space, where the axis is +z, to world space.
+    fn frame(&self) -> DMat3 {
+        let n = self.axis.normalize();
+        let (t, b) = orthonormal_basis(&n);
+        DMat3::from_columns(&[t, b, n])
+    }
+
+    /// Every intersection of the ray with the surface, including those behind the origin.
+    pub(super) fn all_hits(&self, ray: &Ray) -> Vec<HitRecord> {
+        let frame = self.frame();
+        let to_local = frame.transpose();
+        let (big_r, small_r) = (self.major_radius, self.minor_radius);
+
+        let scale = ray.direction.norm();
+        let d = to_local * ray.direction / scale;
+        let o = to_local * (ray.origin - self.center);
+
+        // Start from the bounding sphere to keep the quartic well conditioned.
+        let bound = big_r + small_r;
+        let sphere_tois = solve_quadratic([o.norm_squared() - bound * bound, 2.0 * o.dot(&d), 1.0]);
+        let t_start = match sphere_tois.iter().copied().reduce(f64::min) {
+            Some(t) => t,
+            None => return vec![],
+        };
+        let o = o + t_start * d;
+
+        // (|p|^2 + R^2 - r^2)^2 = 4R^2 (px^2 + py^2) with p = o + t * d and |d| = 1
+        let e = o.norm_squared() + big_r * big_r - small_r * small_r;
+        let f = o.dot(&d);
+        let four_r2 = 4.0 * big_r * big_r;
+        let coeffs = [
+            e * e - four_r2 * (o.x * o.x + o.y * o.y),
+            4.0 * f * e - 2.0 * four_r2 * (o.x * d.x + o.y * d.y),
+            4.0 * f * f + 2.0 * e - four_r2 * (d.x * d.x + d.y * d.y),
+            4.0 * f,
+            1.0,
+        ];
+
+        let mut hits = solve_quartic(coeffs)
+            .into_iter()
+            .map(|t_local| {
+                let p = o + t_local * d;
+                let grad = p * (4.0 * (p.norm_squared() + big_r * big_r - small_r * small_r))
+                    - DVec3::new(p.x, p.y, 0.0) * (2.0 * four_r2);
+                let toi = (t_start + t_local) / scale;
+                HitRecord::new(toi, ray.origin + toi * ray.direction, (frame * grad).normalize())
+            })
+            .collect::<Vec<_>>();
+        hits.sort_by(|a, b| a.toi.partial_cmp(&b.toi).unwrap());
+        hits.dedup_by(|a, b| (a.toi - b.toi).abs() < 1e-9);
+        hits
+    }
+}
+
+impl Bounded for Torus {
+    fn aabb(&self) -> bvh::aabb::AABB {
+        let (min, max) = disk_aabb(&self.center, &self.axis, self.major_radius);
+        let tube = DVec3::repeat(self.minor_radius);
+        aabb_from(&(min - tube), &(max + tube))
+    }
+}
+
+#[typetag::serde]
+impl Shape for Torus {
+    fn hit(&self, ray: &Ray) -> Option<HitRecord> {
+        nearest_hit(self.all_hits(ray))
+    }
+
+    fn material(&self, _hit: &HitRecord) -> Arc<dyn Material> {
+        self.material.clone()
+    }
+
+    fn intervals(&self, ray: &Ray) -> Option<Vec<(HitRecord, HitRecord)>> {
+        // a tangent root touches the surface without crossing it
+        let direction = ray.direction.normalize();
+        let hits = self.all_hits(ray).into_iter().filter(|h| h.normal.dot(&direction).abs() > 1e-6).collect();
+        Some(pair_up(hits))
+    }
+
+    fn is_closed(&self) -> bool {
+        true
+    }
+}
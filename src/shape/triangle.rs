@@ -1,10 +1,9 @@
-use std::{io::BufReader, sync::Arc};
+use std::sync::Arc;
 
 use bvh::aabb::Bounded;
 use glm::{DVec4, DMat4};
 use itertools::Itertools;
 use nalgebra_glm::DVec3;
-use obj::{Obj, Vertex};
 use serde::{Serialize, Deserialize};
 
 use crate::{
@@ -13,91 +12,85 @@ use crate::{
 };
 use nalgebra_glm as glm;
 
-use super::Shape;
+use super::{Shape, position_at, aabb_from};
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Triangle {
     pub points: [DVec3; 3],
     pub material: Arc<dyn Material>,
+    /// Vertices at time 1. The triangle is static if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub points_end: Option<[DVec3; 3]>,
 }
 
 impl Bounded for Triangle {
     fn aabb(&self) -> bvh::aabb::AABB {
-        let min = bvh::Point3::new(
-            self.points
-                .iter()
-                .map(|p| p.x)
-                .min_by(|a, b| a.partial_cmp(b).unwrap())
-                .unwrap() as f32,
-            self.points
-                .iter()
-                .map(|p| p.y)
-                .min_by(|a, b| a.partial_cmp(b).unwrap())
-                .unwrap() as f32,
-            self.points
-                .iter()
-                .map(|p| p.z)
-                .min_by(|a, b| a.partial_cmp(b).unwrap())
-                .unwrap() as f32,
+        let all_points = self.points.iter().chain(self.points_end.iter().flatten());
+        let (min, max) = all_points.fold(
+            (DVec3::repeat(f64::INFINITY), DVec3::repeat(f64::NEG_INFINITY)),
+            |(min, max), p| (glm::min2(&min, p), glm::max2(&max, p)),
         );
-        let max = bvh::Point3::new(
-            self.points
-                .iter()
-                .map(|p| p.x)
-                .max_by(|a, b| a.partial_cmp(b).unwrap())
-                .unwrap() as f32,
-            self.points
-                .iter()
-                .map(|p| p.y)
-                .max_by(|a, b| a.partial_cmp(b).unwrap())
-                .unwrap() as f32,
-            self.points
-                .iter()
-                .map(|p| p.z)
-                .max_by(|a, b| a.partial_cmp(b).unwrap())
-                .unwrap() as f32,
-        );
-        bvh::aabb::AABB { min, max }
+        aabb_from(&min, &max)
     }
 }
 
 impl Triangle {
-    fn v1(&self) -> DVec3 {
-        self.points[1] - self.points[0]
+    pub fn new(points: [DVec3; 3], material: Arc<dyn Material>) -> Self {
+        Self { points, material, points_end: None }
+    }
+
+    /// Apply `transform` to the triangle. If `transform_end` is given, the triangle moves
+    /// from its pose under `transform` at time 0 to its pose under `transform_end` at time 1.
+    pub fn transformed(&self, transform: &DMat4, transform_end: Option<&DMat4>) -> Self {
+        let apply = |m: &DMat4| self.points.map(|p| (m * DVec4::new(p.x, p.y, p.z, 1.0)).xyz());
+        Self {
+            points: apply(transform),
+            material: self.material.clone(),
+            points_end: transform_end.map(apply),
+        }
+    }
+
+    fn points_at(&self, time: f64) -> [DVec3; 3] {
+        match self.points_end {
+            Some(ref points_end) => [0, 1, 2].map(|i| position_at(&self.points[i], &Some(points_end[i]), time)),
+            None => self.points,
+        }
+    }
+}
+
+/// Toi and barycentric coordinates (k1, k2) of `points[1]` and `points[2]` where the ray hits the triangle.
+pub(crate) fn intersect_triangle(ray: &Ray, points: &[DVec3; 3]) -> Option<(f64, (f64, f64))> {
+    // p0 + k1v1 + k2v2 = o + k*d
+    /*
+        [v1 v2 -d] * [k1 k2 k]^T = o - p0
+        [k1 k2 k]^T = [v1 v2 -d]^{-1} * (o - p0)
+    */
+    let (v1, v2) = (points[1] - points[0], points[2] - points[0]);
+    let m = glm::DMat3::from_columns(&[v1, v2, -ray.direction]);
+    let m_inv = m.try_inverse()?;
+    let res = m_inv * (ray.origin - points[0]);
+    let (k1, k2, toi) = (res.x, res.y, res.z);
+    if !(0.0..=1.0).contains(&k1) {
+        return None;
+    }
+    if !(0.0..=1.0).contains(&k2) {
+        return None;
     }
-    fn v2(&self) -> DVec3 {
-        self.points[2] - self.points[0]
+    if k1 + k2 > 1.0 {
+        return None;
     }
+    Some((toi, (k1, k2)))
 }
 
 #[typetag::serde]
 impl Shape for Triangle {
     fn hit(&self, ray: &Ray) -> Option<HitRecord> {
-        // p0 + k1v1 + k2v2 = o + k*d
-        /*
-            [v1 v2 -d] * [k1 k2 k]^T = o - p0
-            [k1 k2 k]^T = [v1 v2 -d]^{-1} * (o - p0)
-        */
-        let m = glm::DMat3::from_columns(&[self.v1(), self.v2(), -ray.direction]);
-        let m_inv = match m.try_inverse() {
-            Some(h) => h,
-            None => return None,
-        };
-        let res = m_inv * (ray.origin - self.points[0]);
-        let (k1, k2, toi) = (res.x, res.y, res.z);
-        if k1 < 0.0 || k1 > 1.0 {
-            return None;
-        }
-        if k2 < 0.0 || k2 > 1.0 {
-            return None;
-        }
-        if k1 + k2 > 1.0 {
-            return None;
-        }
+        let points = self.points_at(ray.time);
+        let (toi, _) = intersect_triangle(ray, &points)?;
         let point = ray.origin + toi * ray.direction;
-        let normal = self.v1().cross(&self.v2()).normalize();
-        
-        Some(HitRecord { toi, point, normal })
+        let normal = (points[1] - points[0]).cross(&(points[2] - points[0])).normalize();
+
+        Some(HitRecord::new(toi, point, normal))
     }
 
     fn material(&self, _hit: &HitRecord) -> Arc<dyn Material> {
@@ -105,65 +98,40 @@ impl Shape for Triangle {
     }
 }
 
-pub fn load_triangle(buffer: &[u8], model_matrix: &DMat4, material: Arc<dyn Material>) -> anyhow::Result<Vec<Triangle>> {
-    let obj_contents: Obj<Vertex, usize> = obj::load_obj(BufReader::new(buffer))?;
-    assert!(obj_contents.indices.len() % 3 == 0);
-    let triangle_cnt = obj_contents.indices.len() / 3;
-    let get_point = |i: usize| -> DVec3 {
-        let t = (obj_contents.vertices[i] as Vertex).position;
-        (model_matrix * DVec4::new(t[0] as f64, t[1] as f64, t[2] as f64, 1.0)).xyz()
-    };
-    Ok((0..triangle_cnt)
-        .into_iter()
-        .map(|i| Triangle {
-            material: material.clone(),
-            points: [
-                get_point(obj_contents.indices[3 * i]), 
-                get_point(obj_contents.indices[3 * i + 1]), 
-                get_point(obj_contents.indices[3 * i + 2])
-            ],
-        })
-        .collect_vec())
+fn rect_triangles(points: &[DVec3; 4], material: &Arc<dyn Material>) -> [Triangle; 2] {
+    [
+        Triangle::new([points[0], points[1], points[2]], material.clone()),
+        Triangle::new([points[0], points[2], points[3]], material.clone()),
+    ]
 }
 
 pub fn draw_rect(points: &[DVec3; 4], material: &Arc<dyn Material>) -> [Arc<dyn Shape>; 2] {
-    [
-        Arc::new(Triangle {
-            points: [points[0], points[1], points[2]],
-            material: material.clone()
-        }),
-        Arc::new(Triangle {
-            points: [points[0], points[2], points[3]],
-            material: material.clone() 
-        }),
-    ]
+    rect_triangles(points, material).map(|t| Arc::new(t) as Arc<dyn Shape>)
 }
 
-pub fn draw_cube(transform: &DMat4, material: &Arc<dyn Material>) -> Vec<Arc<dyn Shape>> {
+/// Draw the unit cube under `transform`. If `transform_end` is given, the cube moves
+/// to its pose under `transform_end` at time 1.
+pub fn draw_cube(transform: &DMat4, transform_end: Option<&DMat4>, material: &Arc<dyn Material>) -> Vec<Arc<dyn Shape>> {
     let h = 0.5; // default to unit cube
 
-    let t = |v: &DVec3| -> DVec3 {
-        (transform * DVec4::new(v.x, v.y, v.z, 1.0)).xyz()
-    };
-
-    let front = draw_rect(&[
-        t(&DVec3::new(-h, h, h)), t(&DVec3::new(-h, -h, h)), t(&DVec3::new(h, -h, h)), t(&DVec3::new(h, h, h)),
-    ], material).into_iter();
-    let back = draw_rect(&[
-        t(&DVec3::new(-h, h, -h)), t(&DVec3::new(h, h, -h)), t(&DVec3::new(h, -h, -h)), t(&DVec3::new(-h, -h, -h)), 
-    ], material).into_iter();
-    let right = draw_rect(&[
-        t(&DVec3::new(h, h, h)), t(&DVec3::new(h, -h, h)), t(&DVec3::new(h, -h, -h)), t(&DVec3::new(h, h, -h))
-    ], material).into_iter();
-    let left = draw_rect(&[
-        t(&DVec3::new(-h, h, h)), t(&DVec3::new(-h, h, -h)), t(&DVec3::new(-h, -h, -h)), t(&DVec3::new(-h, -h, h))
-    ], material).into_iter();
-    let top = draw_rect(&[
-        t(&DVec3::new(-h, h, -h)), t(&DVec3::new(-h, h, h)), t(&DVec3::new(h, h, h)), t(&DVec3::new(h, h, -h))
-    ], material).into_iter();
-    let bottom = draw_rect(&[
-        t(&DVec3::new(-h, -h, -h)), t(&DVec3::new(h, -h, -h)), t(&DVec3::new(h, -h, h)), t(&DVec3::new(-h, -h, h))
-    ], material).into_iter();
-
-    back.chain(front).chain(left).chain(right).chain(bottom).chain(top).collect_vec()
-}
\ No newline at end of file
+    let faces = [
+        // back
+        [DVec3::new(-h, h, -h), DVec3::new(h, h, -h), DVec3::new(h, -h, -h), DVec3::new(-h, -h, -h)],
+        // front
+        [DVec3::new(-h, h, h), DVec3::new(-h, -h, h), DVec3::new(h, -h, h), DVec3::new(h, h, h)],
+        // left
+        [DVec3::new(-h, h, h), DVec3::new(-h, h, -h), DVec3::new(-h, -h, -h), DVec3::new(-h, -h, h)],
+        // right
+        [DVec3::new(h, h, h), DVec3::new(h, -h, h), DVec3::new(h, -h, -h), DVec3::new(h, h, -h)],
+        // bottom
+        [DVec3::new(-h, -h, -h), DVec3::new(h, -h, -h), DVec3::new(h, -h, h), DVec3::new(-h, -h, h)],
+        // top
+        [DVec3::new(-h, h, -h), DVec3::new(-h, h, h), DVec3::new(h, h, h), DVec3::new(h, h, -h)],
+    ];
+
+    faces
+        .iter()
+        .flat_map(|face| rect_triangles(face, material))
+        .map(|t| Arc::new(t.transformed(transform, transform_end)) as Arc<dyn Shape>)
+        .collect_vec()
+}
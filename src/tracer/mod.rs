@@ -4,26 +4,34 @@ use itertools::Itertools;
 use nalgebra_glm::DVec3;
 
 use crate::hit::{BroadPhase, BroadPhaseShape, HitRecord, Ray};
+use crate::medium::{sample_henyey_greenstein, Medium, Volume};
 use crate::utils::*;
 
 use nalgebra_glm as glm;
 
 pub struct TracingHelper<'a> {
     obj: &'a Vec<BroadPhaseShape>,
-    broad_phase: Box<dyn BroadPhase>,
+    broad_phase: &'a dyn BroadPhase,
     depth_limit: usize,
+    volumes: &'a [Volume],
+    /// Medium filling all of space.
+    fog: Option<&'a dyn Medium>,
 }
 
 impl<'a> TracingHelper<'a> {
     pub fn new(
         obj: &'a Vec<BroadPhaseShape>,
-        broad_phase: Box<dyn BroadPhase>,
+        broad_phase: &'a dyn BroadPhase,
         depth_limit: usize,
+        volumes: &'a [Volume],
+        fog: Option<&'a dyn Medium>,
     ) -> TracingHelper<'a> {
         TracingHelper {
             obj,
             broad_phase,
             depth_limit,
+            volumes,
+            fog,
         }
     }
 
@@ -39,19 +47,26 @@ impl<'a> TracingHelper<'a> {
         let records = self.ray_intersect(ray);
         let nearest_hit = records.first();
 
+        // light may scatter in a medium before it reaches the surface
+        let surface_toi = nearest_hit.map_or(f64::INFINITY, |(hit, _)| hit.toi);
+        if let Some((toi, albedo, g)) = self.sample_media(ray, surface_toi) {
+            let scattered = Ray::new(ray.origin + toi * ray.direction, sample_henyey_greenstein(&ray.direction, g));
+            return albedo.component_mul(&self.trace(&scattered.with_time(ray.time), depth - 1));
+        }
+
         match nearest_hit {
             Some(hit_info) => {
                 let (hit, bf_shape) = hit_info;
-                let material = bf_shape.shape.material(&hit);
+                let material = bf_shape.shape.material(hit);
 
                 let sample_count = 1;
                 let mut res = DVec3::zeros();
                 
                 for _i in 0..sample_count {
-                    let rays_scattered = material.scatter(&ray, &hit);
+                    let rays_scattered = material.scatter(ray, hit);
                     let sample_res = rays_scattered.into_iter().map(|(c, r)| 
-                        c.component_mul(&self.trace(&r, depth - 1))
-                    ).sum::<DVec3>() + material.emit(&ray, &hit);
+                        c.component_mul(&self.trace(&r.with_time(ray.time), depth - 1))
+                    ).sum::<DVec3>() + material.emit(ray, hit);
                     res += sample_res / (sample_count as f64);
                 }
 
@@ -67,17 +82,26 @@ impl<'a> TracingHelper<'a> {
         }
     }
 
+    /// The nearest scattering event in any medium before `t_max`, with its albedo and phase asymmetry.
+    fn sample_media(&self, ray: &Ray, t_max: f64) -> Option<(f64, DVec3, f64)> {
+        let t_min = 1e-8;
+        let fog = self.fog.and_then(|m| m.sample_scatter(ray, t_min, t_max).map(|(t, a)| (t, a, m.asymmetry())));
+        self.volumes.iter()
+            .filter_map(|v| v.sample_scatter(ray, t_min, t_max).map(|(t, a)| (t, a, v.medium.asymmetry())))
+            .chain(fog)
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+    }
+
     fn ray_intersect(&self, ray: &Ray) -> Vec<(HitRecord, &BroadPhaseShape)> {
         self.ray_intersect_with_bound(ray, (1e-8, 1e8))
     }
 
     fn ray_intersect_with_bound(&self, ray: &Ray, bound: (f64, f64)) -> Vec<(HitRecord, &BroadPhaseShape)> {
-      let filtered = self.broad_phase.trace(&self.obj, ray);
-      let records = filtered
+      let filtered = self.broad_phase.trace(self.obj, ray);
+      filtered
           .into_iter()
-          .flat_map(|x| x.shape.hit_with_bound(ray, bound).and_then(|h| Some((h, x))))
+          .flat_map(|x| x.shape.hit_with_bound(ray, bound).map(|h| (h, x)))
           .sorted_by(|x, y| x.0.toi.partial_cmp(&y.0.toi).unwrap())
-          .collect_vec();
-      records
+          .collect_vec()
   }
 }
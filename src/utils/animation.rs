@@ -0,0 +1,155 @@
+use nalgebra_glm::{DMat4, DVec3};
+use nalgebra_glm as glm;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Values that can be blended between keyframes.
+pub trait Interpolate {
+    /// Blend from `self` at t = 0 to `other` at t = 1. `t` may lie outside of [0, 1].
+    fn interpolate(&self, other: &Self, t: f64) -> Self;
+}
+
+impl Interpolate for f64 {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for DVec3 {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        glm::lerp(self, other, t)
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+pub enum Interpolation {
+    /// Hold the value of the previous keyframe.
+    Step,
+    #[default]
+    Linear,
+    /// Catmull-Rom spline through the keyframes.
+    Smooth,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Keyframe<T> {
+    /// Time in seconds.
+    pub time: f64,
+    pub value: T,
+}
+
+/// Keyframed value. Keyframes must be sorted by time.
+/// Before the first and after the last keyframe the value is held constant.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Animation<T> {
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    #[serde(deserialize_with = "sorted_keyframes", bound(deserialize = "T: Deserialize<'de>"))]
+    pub keyframes: Vec<Keyframe<T>>,
+}
+
+fn sorted_keyframes<'de, D, T>(deserializer: D) -> Result<Vec<Keyframe<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    let keyframes = Vec::<Keyframe<T>>::deserialize(deserializer)?;
+    if let Some(k) = keyframes.iter().find(|k| !k.time.is_finite()) {
+        return Err(serde::de::Error::custom(format!("keyframe time {} is not finite", k.time)));
+    }
+    if let Some(pair) = keyframes.windows(2).find(|pair| pair[0].time > pair[1].time) {
+        return Err(serde::de::Error::custom(format!(
+            "keyframes must be sorted by time, but {} comes before {}", pair[0].time, pair[1].time
+        )));
+    }
+    Ok(keyframes)
+}
+
+impl<T: Interpolate + Clone> Animation<T> {
+    /// Value at `time`, or None if there are no keyframes.
+    pub fn sample(&self, time: f64) -> Option<T> {
+        let keys = &self.keyframes;
+        let next = keys.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return keys.first().map(|k| k.value.clone());
+        }
+        if next == keys.len() {
+            return keys.last().map(|k| k.value.clone());
+        }
+
+        let (k1, k2) = (&keys[next - 1], &keys[next]);
+        let t = (time - k1.time) / (k2.time - k1.time);
+        Some(match self.interpolation {
+            Interpolation::Step => k1.value.clone(),
+            Interpolation::Linear => k1.value.interpolate(&k2.value, t),
+            Interpolation::Smooth => {
+                // Barry-Goldman pyramid for a uniform Catmull-Rom segment between p1 and p2
+                let p0 = &keys[next.saturating_sub(2)].value;
+                let p3 = &keys[(next + 1).min(keys.len() - 1)].value;
+                let (p1, p2) = (&k1.value, &k2.value);
+                let a1 = p0.interpolate(p1, t + 1.0);
+                let a2 = p1.interpolate(p2, t);
+                let a3 = p2.interpolate(p3, t - 1.0);
+                let b1 = a1.interpolate(&a2, (t + 1.0) / 2.0);
+                let b2 = a2.interpolate(&a3, t / 2.0);
+                b1.interpolate(&b2, t)
+            }
+        })
+    }
+}
+
+/// Translation, rotation and scale of a model.
+/// Rotation is given in degrees around x, then y, then z, so that turntables can spin past 360°.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transform {
+    #[serde(default = "DVec3::zeros")]
+    pub translation: DVec3,
+    #[serde(default = "DVec3::zeros")]
+    pub rotation: DVec3,
+    #[serde(default = "default_scale")]
+    pub scale: DVec3,
+}
+
+fn default_scale() -> DVec3 {
+    DVec3::new(1.0, 1.0, 1.0)
+}
+
+impl Transform {
+    pub fn to_matrix(&self) -> DMat4 {
+        let r = self.rotation.map(f64::to_radians);
+        glm::translation(&self.translation)
+            * glm::rotation(r.z, &DVec3::new(0.0, 0.0, 1.0))
+            * glm::rotation(r.y, &DVec3::new(0.0, 1.0, 0.0))
+            * glm::rotation(r.x, &DVec3::new(1.0, 0.0, 0.0))
+            * glm::scaling(&self.scale)
+    }
+}
+
+impl Interpolate for Transform {
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        Self {
+            translation: self.translation.interpolate(&other.translation, t),
+            rotation: self.rotation.interpolate(&other.rotation, t),
+            scale: self.scale.interpolate(&other.scale, t),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsorted_keyframes_are_rejected() {
+        let json = r#"{"keyframes": [{"time": 1.0, "value": 0.0}, {"time": 0.0, "value": 1.0}]}"#;
+        assert!(serde_json::from_str::<Animation<f64>>(json).is_err());
+    }
+
+    #[test]
+    fn linear_sample_is_held_outside_the_keyframes() {
+        let json = r#"{"keyframes": [{"time": 0.0, "value": 0.0}, {"time": 2.0, "value": 1.0}]}"#;
+        let animation: Animation<f64> = serde_json::from_str(json).unwrap();
+        assert_eq!(animation.sample(-1.0), Some(0.0));
+        assert_eq!(animation.sample(1.0), Some(0.5));
+        assert_eq!(animation.sample(3.0), Some(1.0));
+    }
+}
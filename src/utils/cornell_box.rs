@@ -30,8 +30,7 @@ pub fn cornell_box() -> Vec<Arc<dyn Shape>> {
                 DVec3::new(-2.0, 1.0, 1.0),
             ],
             &green,
-        )
-        .into_iter(),
+        ),
     );
 
     world.extend(
@@ -43,8 +42,7 @@ pub fn cornell_box() -> Vec<Arc<dyn Shape>> {
                 DVec3::new(2.0, -1.0, -2.0),
             ],
             &red,
-        )
-        .into_iter(),
+        ),
     );
 
     world.extend(
@@ -56,8 +54,7 @@ pub fn cornell_box() -> Vec<Arc<dyn Shape>> {
                 DVec3::new(2.0, -1.0, -2.0),
             ],
             &wood,
-        )
-        .into_iter(),
+        ),
     );
 
     world.extend(
@@ -69,8 +66,7 @@ pub fn cornell_box() -> Vec<Arc<dyn Shape>> {
                 DVec3::new(2.0, 1.0, -2.0),
             ],
             &white,
-        )
-        .into_iter(),
+        ),
     );
 
     world.extend(
@@ -82,8 +78,7 @@ pub fn cornell_box() -> Vec<Arc<dyn Shape>> {
                 DVec3::new(-2.0, -1.0, 1.0),
             ],
             &white,
-        )
-        .into_iter(),
+        ),
     );
 
     world.extend(
@@ -95,8 +90,7 @@ pub fn cornell_box() -> Vec<Arc<dyn Shape>> {
                 DVec3::new(-1.0, 1.0, 0.8),
             ],
             &(Arc::new(Light::new(DVec3::new(1.0, 1.0, 0.8), 5.0)) as Arc<dyn Material>),
-        )
-        .into_iter(),
+        ),
     );
 
     world.extend(
@@ -108,8 +102,7 @@ pub fn cornell_box() -> Vec<Arc<dyn Shape>> {
                 DVec3::new(-2.0, 1.0, 1.0),
             ],
             &white,
-        )
-        .into_iter(),
+        ),
     );
     world
 }
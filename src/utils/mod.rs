@@ -3,8 +3,16 @@ mod color;
 mod cornell_box;
 mod vec;
 mod scene_info;
+mod animation;
+mod output_path;
+mod polynomial;
+mod scene_dir;
 
 pub use color::*;
 pub use vec::*;
 pub use cornell_box::*;
-pub use scene_info::*;
\ No newline at end of file
+pub use scene_info::*;
+pub use animation::*;
+pub use output_path::*;
+pub use polynomial::*;
+pub use scene_dir::*;
\ No newline at end of file
@@ -0,0 +1,64 @@
+/// Substitute `frame` into the first `%d` or `%0Nd` of `pattern`.
+/// If there is none and `sequence` is set, `_NNNN` is appended to the file stem instead,
+/// so that the frames of a sequence do not overwrite each other.
+pub fn format_frame_path(pattern: &str, frame: u32, sequence: bool) -> String {
+    for (start, _) in pattern.match_indices('%') {
+        let rest = &pattern[start + 1..];
+        let spec_len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        if rest[spec_len..].starts_with('d') {
+            let width = rest[..spec_len].parse::<usize>().unwrap_or(0);
+            let end = start + 1 + spec_len + 1;
+            return format!("{}{:0width$}{}", &pattern[..start], frame, &pattern[end..], width = width);
+        }
+    }
+    if !sequence {
+        return pattern.to_string();
+    }
+    append_to_stem(pattern, &format!("_{:04}", frame))
+}
+
+/// Substitute the camera `name` for `%c` in `pattern`.
+/// If there is none and several cameras are rendered, `_<name>` is appended to the file stem instead.
+pub fn format_camera_path(pattern: &str, name: &str, multiple: bool) -> String {
+    if pattern.contains("%c") {
+        pattern.replace("%c", name)
+    } else if multiple {
+        append_to_stem(pattern, &format!("_{}", name))
+    } else {
+        pattern.to_string()
+    }
+}
+
+fn append_to_stem(path: &str, suffix: &str) -> String {
+    match path.rfind('.') {
+        Some(dot) if !path[dot..].contains('/') => format!("{}{}{}", &path[..dot], suffix, &path[dot..]),
+        _ => format!("{}{}", path, suffix),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_replaces_the_first_placeholder() {
+        assert_eq!(format_frame_path("out/frame_%04d.png", 7, true), "out/frame_0007.png");
+        assert_eq!(format_frame_path("frame_%d.png", 12, false), "frame_12.png");
+        assert_eq!(format_frame_path("%d_%d.png", 3, true), "3_%d.png");
+        assert_eq!(format_frame_path("100%_%2d.png", 5, true), "100%_05.png");
+    }
+
+    #[test]
+    fn sequences_without_placeholder_number_the_stem() {
+        assert_eq!(format_frame_path("out.png", 3, true), "out_0003.png");
+        assert_eq!(format_frame_path("out.png", 3, false), "out.png");
+        assert_eq!(format_frame_path("renders.d/out", 3, true), "renders.d/out_0003");
+    }
+
+    #[test]
+    fn cameras_are_named_in_the_path() {
+        assert_eq!(format_camera_path("%c/out.png", "side", true), "side/out.png");
+        assert_eq!(format_camera_path("out.png", "side", true), "out_side.png");
+        assert_eq!(format_camera_path("out.png", "main", false), "out.png");
+    }
+}
@@ -0,0 +1,189 @@
+//! Real roots of low order polynomials, after Jochen Schwarze, "Cubic and Quartic Roots", Graphics Gems.
+//! Coefficients are given from the constant term up.
+
+use std::f64::consts::PI;
+
+const EPS: f64 = 1e-9;
+
+/// Size of the roots of the monic polynomial with the lower coefficients `c`, constant term first.
+/// Terms of the degree k in the roots are compared against this to the power k, so that the
+/// tolerances hold for shapes of any size.
+fn root_scale(c: &[f64]) -> f64 {
+    let n = c.len();
+    c.iter().enumerate().map(|(k, x)| x.abs().powf(1.0 / (n - k) as f64)).fold(0.0, f64::max)
+}
+
+fn is_zero(x: f64, scale: f64) -> bool {
+    x.abs() <= EPS * scale
+}
+
+/// Real roots of c[2] x^2 + c[1] x + c[0], in no particular order.
+pub fn solve_quadratic(c: [f64; 3]) -> Vec<f64> {
+    if c[2] == 0.0 {
+        return if c[1] == 0.0 { vec![] } else { vec![-c[0] / c[1]] };
+    }
+    let p = c[1] / (2.0 * c[2]);
+    let q = c[0] / c[2];
+    let d = p * p - q;
+    let scale = root_scale(&[q, 2.0 * p]);
+    if is_zero(d, scale * scale) {
+        vec![-p]
+    } else if d < 0.0 {
+        vec![]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![sqrt_d - p, -sqrt_d - p]
+    }
+}
+
+/// Real roots of c[3] x^3 + c[2] x^2 + c[1] x + c[0], in no particular order.
+pub fn solve_cubic(c: [f64; 4]) -> Vec<f64> {
+    if c[3] == 0.0 {
+        return solve_quadratic([c[0], c[1], c[2]]);
+    }
+    // normal form: x^3 + Ax^2 + Bx + C = 0
+    let a = c[2] / c[3];
+    let b = c[1] / c[3];
+    let cc = c[0] / c[3];
+
+    // substitute x = y - A/3 to eliminate the quadric term: y^3 + 3py + 2q = 0
+    let sq_a = a * a;
+    let p = (-sq_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - a * b / 3.0 + cc) / 2.0;
+
+    let cb_p = p * p * p;
+    let d = q * q + cb_p;
+    let cb_scale = root_scale(&[cc, b, a]).powi(3);
+
+    let roots = if is_zero(d, cb_scale * cb_scale) {
+        if is_zero(q, cb_scale) {
+            vec![0.0]
+        } else {
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if d < 0.0 {
+        let phi = (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        vec![t * phi.cos(), -t * (phi + PI / 3.0).cos(), -t * (phi - PI / 3.0).cos()]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt()]
+    };
+
+    roots.into_iter().map(|y| y - a / 3.0).collect()
+}
+
+/// Real roots of c[4] x^4 + c[3] x^3 + c[2] x^2 + c[1] x + c[0], in no particular order.
+/// Roots are polished with a few Newton steps, as the closed form loses precision.
+pub fn solve_quartic(c: [f64; 5]) -> Vec<f64> {
+    if c[4] == 0.0 {
+        return solve_cubic([c[0], c[1], c[2], c[3]]);
+    }
+    // normal form: x^4 + Ax^3 + Bx^2 + Cx + D = 0
+    let a = c[3] / c[4];
+    let b = c[2] / c[4];
+    let cc = c[1] / c[4];
+    let d = c[0] / c[4];
+
+    // substitute x = y - A/4 to eliminate the cubic term: y^4 + py^2 + qy + r = 0
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = sq_a * a / 8.0 - a * b / 2.0 + cc;
+    let r = -3.0 / 256.0 * sq_a * sq_a + sq_a * b / 16.0 - a * cc / 4.0 + d;
+    let sq_scale = root_scale(&[d, cc, b, a]).powi(2);
+
+    let roots = if is_zero(r, sq_scale * sq_scale) {
+        // no absolute term: y(y^3 + py + q) = 0
+        let mut roots = solve_cubic([q, p, 0.0, 1.0]);
+        roots.push(0.0);
+        roots
+    } else {
+        // solve the resolvent cubic and take its one real root
+        let z = solve_cubic([r * p / 2.0 - q * q / 8.0, -r, -p / 2.0, 1.0])[0];
+
+        // build two quadric equations
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+        let u = if is_zero(u, sq_scale * sq_scale) { 0.0 } else if u > 0.0 { u.sqrt() } else { return vec![] };
+        let v = if is_zero(v, sq_scale) { 0.0 } else if v > 0.0 { v.sqrt() } else { return vec![] };
+
+        let v = if q < 0.0 { -v } else { v };
+        let mut roots = solve_quadratic([z - u, v, 1.0]);
+        roots.extend(solve_quadratic([z + u, -v, 1.0]));
+        roots
+    };
+
+    let f = |x: f64| (((c[4] * x + c[3]) * x + c[2]) * x + c[1]) * x + c[0];
+    let df = |x: f64| ((4.0 * c[4] * x + 3.0 * c[3]) * x + 2.0 * c[2]) * x + c[1];
+    roots
+        .into_iter()
+        .map(|y| {
+            let mut x = y - a / 4.0;
+            for _ in 0..3 {
+                let slope = df(x);
+                if slope != 0.0 {
+                    x -= f(x) / slope;
+                }
+            }
+            x
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(mut roots: Vec<f64>, expected: &[f64]) {
+        roots.sort_by(f64::total_cmp);
+        assert_eq!(roots.len(), expected.len(), "roots {:?}, expected {:?}", roots, expected);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!((root - expected).abs() < 1e-6, "roots {:?}, expected {:?}", roots, expected);
+        }
+    }
+
+    #[test]
+    fn quadratic_roots() {
+        assert_roots(solve_quadratic([3.0, -4.0, 1.0]), &[1.0, 3.0]);
+        assert_roots(solve_quadratic([1.0, -2.0, 1.0]), &[1.0]);
+        assert_roots(solve_quadratic([1.0, 0.0, 1.0]), &[]);
+        assert_roots(solve_quadratic([2.0, 4.0, 0.0]), &[-0.5]);
+    }
+
+    #[test]
+    fn cubic_roots() {
+        // (x - 1)(x - 2)(x - 3)
+        assert_roots(solve_cubic([-6.0, 11.0, -6.0, 1.0]), &[1.0, 2.0, 3.0]);
+        // (x - 1)(x^2 + 1)
+        assert_roots(solve_cubic([-1.0, 1.0, -1.0, 1.0]), &[1.0]);
+        // (x + 2)^3
+        assert_roots(solve_cubic([8.0, 12.0, 6.0, 1.0]), &[-2.0]);
+    }
+
+    #[test]
+    fn quartic_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(solve_quartic([24.0, -50.0, 35.0, -10.0, 1.0]), &[1.0, 2.0, 3.0, 4.0]);
+        // (x^2 - 1)(x^2 + 1)
+        assert_roots(solve_quartic([-1.0, 0.0, 0.0, 0.0, 1.0]), &[-1.0, 1.0]);
+        assert_roots(solve_quartic([1.0, 0.0, 0.0, 0.0, 1.0]), &[]);
+        // x (x - 1)(x - 2)(x - 3), without an absolute term
+        assert_roots(solve_quartic([0.0, -6.0, 11.0, -6.0, 1.0]), &[0.0, 1.0, 2.0, 3.0]);
+        // the leading coefficient need not be one
+        assert_roots(solve_quartic([-2.0, 0.0, 0.0, 0.0, 2.0]), &[-1.0, 1.0]);
+    }
+
+    #[test]
+    fn close_roots_of_small_polynomials_stay_apart() {
+        let assert_small_roots = |roots: Vec<f64>, expected: &[f64]| {
+            assert_roots(roots.iter().map(|x| x * 1e4).collect(), &expected.iter().map(|x| x * 1e4).collect::<Vec<_>>());
+        };
+        // (x - 1e-4)(x - 2e-4)
+        assert_small_roots(solve_quadratic([2e-8, -3e-4, 1.0]), &[1e-4, 2e-4]);
+        // (x - 1e-4)(x - 2e-4)(x - 3e-4)
+        assert_small_roots(solve_cubic([-6e-12, 11e-8, -6e-4, 1.0]), &[1e-4, 2e-4, 3e-4]);
+        // (x - 1e-3)(x - 2e-3)(x - 3e-3)(x - 4e-3)
+        assert_small_roots(solve_quartic([24e-12, -50e-9, 35e-6, -10e-3, 1.0]), &[1e-3, 2e-3, 3e-3, 4e-3]);
+    }
+}
@@ -0,0 +1,34 @@
+use std::{cell::RefCell, path::{Path, PathBuf}};
+
+thread_local! {
+    static SCENE_DIR: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, which reads a scene file in `dir`, with the files it names resolved against `dir`.
+/// Serde gives no context to the types being read, so they look the directory up with [`scene_path`].
+pub fn with_scene_dir<T>(dir: &Path, f: impl FnOnce() -> T) -> T {
+    let previous = SCENE_DIR.with(|d| d.replace(Some(dir.to_path_buf())));
+    let result = f();
+    SCENE_DIR.with(|d| *d.borrow_mut() = previous);
+    result
+}
+
+/// `path` relative to the scene file being read, or unchanged outside of [`with_scene_dir`].
+pub fn scene_path(path: &Path) -> PathBuf {
+    SCENE_DIR.with(|d| match &*d.borrow() {
+        Some(dir) => dir.join(path),
+        None => path.to_path_buf(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_relative_to_the_scene_only_while_reading_it() {
+        let inside = with_scene_dir(Path::new("scenes"), || scene_path(Path::new("wood.png")));
+        assert_eq!(inside, Path::new("scenes/wood.png"));
+        assert_eq!(scene_path(Path::new("wood.png")), Path::new("wood.png"));
+    }
+}
@@ -1,29 +1,223 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-
+use anyhow::Context;
 use nalgebra_glm::{DMat4, DVec3};
 use nalgebra_glm as glm;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::camera::{Screen, Camera};
-use crate::material::{Metal, Dielectric, self, Light};
+use crate::camera::{Screen, Camera, Region, View};
+use crate::medium::{Medium, Volume};
+use crate::material::{Metal, Dielectric, Diffuse, self, Light, ImageTexture};
 use crate::utils::WHITE;
-use crate::{shape::{Sphere, Shape, load_triangle, draw_cube}, material::{Material, Wood}};
+use crate::{shape::{Sphere, Shape, Instance, Curve, CurveType, load_obj, load_mesh_file, refine_mesh, draw_cube}, material::{Material, Wood}};
 
-use super::GREEN;
+use super::{Animation, Transform, GREEN};
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ModelInfo {
     pub transform: DMat4,
-    pub material: Arc<dyn Material>
+    pub material: Arc<dyn Material>,
+    /// Transform at time 1. The model is static if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform_end: Option<DMat4>,
+    /// Keyframed transform. Overrides `transform` and `transform_end` when given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub animation: Option<Animation<Transform>>,
+}
+
+impl ModelInfo {
+    pub fn new(transform: DMat4, material: Arc<dyn Material>) -> Self {
+        Self { transform, material, transform_end: None, animation: None }
+    }
+
+    /// The model at `time`, moving to its pose at `time + frame_duration` over the shutter interval.
+    pub fn at_time(&self, time: f64, frame_duration: f64) -> ModelInfo {
+        let mut model = self.clone();
+        if let Some((transform, transform_end)) = animated_transform(&self.animation, time, frame_duration) {
+            model.transform = transform;
+            model.transform_end = transform_end;
+        }
+        model
+    }
+}
+
+/// Transforms at `time` and `time + frame_duration`, if there are keyframes.
+fn animated_transform(animation: &Option<Animation<Transform>>, time: f64, frame_duration: f64) -> Option<(DMat4, Option<DMat4>)> {
+    let animation = animation.as_ref()?;
+    let transform = animation.sample(time)?.to_matrix();
+    Some((transform, animation.sample(time + frame_duration).map(|t| t.to_matrix())))
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MeshInfo {
+    /// OBJ, PLY or STL file, relative to the scene file.
+    pub path: PathBuf,
+    pub transform: DMat4,
+    /// Replaces the materials from the MTL files of OBJ models when given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub material: Option<Arc<dyn Material>>,
+    /// Transform at time 1. The model is static if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform_end: Option<DMat4>,
+    /// Keyframed transform. Overrides `transform` and `transform_end` when given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub animation: Option<Animation<Transform>>,
+    /// Levels of Loop subdivision, each splitting every triangle in four.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub subdivision: u32,
+    /// Offset of the surface along its normals, applied after subdivision.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub displacement: Option<Displacement>,
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Displacement {
+    /// Image relative to the scene file, read through the UVs of the mesh. Brighter is farther out.
+    pub texture: PathBuf,
+    /// Offset at white, in the units of the mesh.
+    pub scale: f64,
+}
+
+impl MeshInfo {
+    /// The mesh at `time`, see [`ModelInfo::at_time`].
+    pub fn at_time(&self, time: f64, frame_duration: f64) -> MeshInfo {
+        let mut mesh = self.clone();
+        if let Some((transform, transform_end)) = animated_transform(&self.animation, time, frame_duration) {
+            mesh.transform = transform;
+            mesh.transform_end = transform_end;
+        }
+        mesh
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InstanceInfo {
+    /// Name of the shape in `SceneInfo::objects`.
+    pub object: String,
+    pub transform: DMat4,
+    /// Transform at time 1. The instance is static if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform_end: Option<DMat4>,
+    /// Replaces the materials of the object when given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub material: Option<Arc<dyn Material>>,
+}
+
+/// A strand of cubic Bézier segments joined end to end, such as a hair or a blade of grass.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CurveInfo {
+    /// Control points, four for the first segment and three more for each following one.
+    pub points: Vec<DVec3>,
+    /// Width at the root and at the tip, varying linearly along the strand.
+    pub width: [f64; 2],
+    #[serde(default)]
+    pub curve_type: CurveType,
+    pub material: Arc<dyn Material>,
+}
+
+impl CurveInfo {
+    /// One shape per segment, so that the broad phase can tell them apart.
+    fn segments(&self) -> anyhow::Result<Vec<Arc<dyn Shape>>> {
+        anyhow::ensure!(self.points.len() >= 4 && (self.points.len() - 1).is_multiple_of(3),
+            "curves need 3n + 1 control points, got {}", self.points.len());
+        let count = (self.points.len() - 1) / 3;
+        let width_at = |k: usize| self.width[0] + (self.width[1] - self.width[0]) * k as f64 / count as f64;
+        Ok((0..count).map(|k| Arc::new(Curve {
+            points: [self.points[3 * k], self.points[3 * k + 1], self.points[3 * k + 2], self.points[3 * k + 3]],
+            width: [width_at(k), width_at(k + 1)],
+            curve_type: self.curve_type,
+            material: self.material.clone(),
+        }) as Arc<dyn Shape>).collect())
+    }
+}
+
+/// Mesh file, levels of subdivision, and displacement texture with the bits of its scale.
+type MeshKey = (PathBuf, u32, Option<(PathBuf, u64)>);
+
+/// Meshes loaded by [`SceneInfo::split_to_shape`], kept between the frames of an animation.
+#[derive(Default)]
+pub struct MeshCache {
+    bunny: Option<Arc<dyn Shape>>,
+    meshes: HashMap<MeshKey, Arc<dyn Shape>>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NamedCamera {
+    pub name: String,
+    pub camera: Camera,
+    /// When given, the camera is rendered as a stereo pair `<name>_left` and `<name>_right`
+    /// with this distance between the eyes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interocular_distance: Option<f64>,
 }
 
+/// Name of the view rendered from `SceneInfo::camera`.
+pub const MAIN_CAMERA: &str = "main";
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct SceneInfo {
     pub camera: Camera,
     pub spheres: Vec<Sphere>,
     pub cubes: Vec<ModelInfo>,
-    pub bunnies: Vec<ModelInfo>
+    pub bunnies: Vec<ModelInfo>,
+    /// Models loaded from files. Entries with the same path share one copy of the mesh.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub meshes: Vec<MeshInfo>,
+    /// Any other shapes, tagged by type.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shapes: Vec<Arc<dyn Shape>>,
+    /// Shapes by name that are only rendered where `instances` place them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub objects: BTreeMap<String, Arc<dyn Shape>>,
+    /// Copies of `objects` sharing their geometry.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub instances: Vec<InstanceInfo>,
+    /// Hair, fur and grass strands.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub curves: Vec<CurveInfo>,
+    /// Participating media inside closed shapes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub volumes: Vec<Volume>,
+    /// Medium filling all of space on top of `volumes`, such as atmospheric haze.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fog: Option<Arc<dyn Medium>>,
+    /// Frames per second of rendered animations.
+    #[serde(default = "default_fps", deserialize_with = "positive_fps")]
+    pub fps: f64,
+    /// Keyframed camera. Overrides `camera` when given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera_animation: Option<Animation<Camera>>,
+    /// Additional cameras that can be rendered instead of or along with `camera`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cameras: Vec<NamedCamera>,
+    /// Whether the shapes are placed in the built-in Cornell box.
+    #[serde(default = "default_cornell_box")]
+    pub cornell_box: bool,
+    /// Only trace this window of the frame.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<Region>,
+}
+
+fn default_fps() -> f64 {
+    24.0
+}
+
+fn positive_fps<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let fps = f64::deserialize(deserializer)?;
+    if !(fps.is_finite() && fps > 0.0) {
+        return Err(serde::de::Error::custom(format!("fps must be positive, got {}", fps)));
+    }
+    Ok(fps)
+}
+
+fn default_cornell_box() -> bool {
+    true
 }
 
 impl Default for SceneInfo {
@@ -31,11 +225,15 @@ impl Default for SceneInfo {
         let screen = Screen::new(400, 300);
         let camera = Camera {
             screen,
-            viewport_height: 2.0 * 3.0f64.sqrt(),
-            origin: glm::DVec3::new(0.0, 0.0, 0.0),
-            focal_len: 1.0,
-            pitch: 0.0,
-            yaw: 0.0,
+            view: View::PitchYaw {
+                viewport_height: 2.0 * 3.0f64.sqrt(),
+                origin: glm::DVec3::new(0.0, 0.0, 0.0),
+                focal_len: 1.0,
+                pitch: 0.0,
+                yaw: 0.0,
+                roll: 0.0,
+            },
+            ..Default::default()
         };
         let wood: Arc<dyn Material> = Arc::new(
             Wood::new(DVec3::new(0.0, -0.75, -0.5), DVec3::new(0.0, 1.0, 1.0), DVec3::new(0.0, 0.0, 1.0), material::WoodType::RedWood)
@@ -46,40 +244,146 @@ impl Default for SceneInfo {
         let cube_transform = glm::translation(&DVec3::new(1.0, -0.25, -1.4))
         * glm::rotation(-1.0, &DVec3::new(0.0, 1.5, 0.0))
         * glm::scaling(&DVec3::new(0.5, 1.0, 0.5));
-        let glass_ball = Sphere {
-            center: DVec3::new(0.0, -0.75, -1.3),
-            radius: 0.25,
-            material: glass,
-        };
-        let metal_ball = Sphere {
-            center: DVec3::new(-1.0, -0.75, -1.0),
-            radius: 0.25,
-            material: metal.clone()
-        };
-        let light_ball = Sphere {
-            center: DVec3::new(1.5, -0.975, -1.3),
-            radius: 0.05,
-            material: Arc::new(Light::new(GREEN, 100.0))
-        };
-        let scene = SceneInfo {
+        let glass_ball = Sphere::new(DVec3::new(0.0, -0.75, -1.3), 0.25, glass);
+        let metal_ball = Sphere::new(DVec3::new(-1.0, -0.75, -1.0), 0.25, metal.clone());
+        let light_ball = Sphere::new(DVec3::new(1.5, -0.975, -1.3), 0.05, Arc::new(Light::new(GREEN, 100.0)));
+        SceneInfo {
             camera,
-            bunnies: vec![ModelInfo { transform: bunny_transform, material: wood }],
-            cubes: vec![ModelInfo { transform: cube_transform, material: metal }],
-            spheres: vec![glass_ball, metal_ball, light_ball]
-        };
-        scene
+            bunnies: vec![ModelInfo::new(bunny_transform, wood)],
+            cubes: vec![ModelInfo::new(cube_transform, metal)],
+            spheres: vec![glass_ball, metal_ball, light_ball],
+            meshes: vec![],
+            shapes: vec![],
+            objects: BTreeMap::new(),
+            instances: vec![],
+            curves: vec![],
+            volumes: vec![],
+            fog: None,
+            fps: default_fps(),
+            camera_animation: None,
+            cameras: vec![],
+            cornell_box: default_cornell_box(),
+            region: None,
+        }
     }
 }
 
 impl SceneInfo {
-    pub fn split_to_shape(&self) -> Vec<Arc<dyn Shape>> {
-        let bunny_model = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/bunny.obj"));
+    /// Snapshot of the scene at `time` in seconds, with keyframed values evaluated.
+    pub fn at_time(&self, time: f64) -> SceneInfo {
+        let frame_duration = 1.0 / self.fps;
+        let camera = self.camera_animation.as_ref()
+            .and_then(|a| a.sample(time))
+            .unwrap_or(self.camera);
+        SceneInfo {
+            camera,
+            cubes: self.cubes.iter().map(|c| c.at_time(time, frame_duration)).collect(),
+            bunnies: self.bunnies.iter().map(|b| b.at_time(time, frame_duration)).collect(),
+            meshes: self.meshes.iter().map(|m| m.at_time(time, frame_duration)).collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Check what serde cannot check while reading a single field.
+    /// Camera names must be unique after stereo rigs are expanded, and `main` is taken by `camera`.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let keyframed = self.camera_animation.iter().flat_map(|a| a.keyframes.iter().map(|k| &k.value));
+        for camera in std::iter::once(&self.camera).chain(keyframed).chain(self.cameras.iter().map(|c| &c.camera)) {
+            camera.validate()?;
+        }
+        let mut names = HashSet::new();
+        for (name, _) in self.views() {
+            anyhow::ensure!(names.insert(name.clone()), "camera name {} is used more than once", name);
+        }
+        Ok(())
+    }
+
+    /// All cameras of the scene by name, with stereo rigs expanded into their two eyes.
+    /// `camera` comes first, named [`MAIN_CAMERA`].
+    pub fn views(&self) -> Vec<(String, Camera)> {
+        let mut views = vec![(MAIN_CAMERA.to_string(), self.camera)];
+        for named in &self.cameras {
+            match named.interocular_distance {
+                Some(distance) => {
+                    let (left, right) = named.camera.stereo_pair(distance);
+                    views.push((format!("{}_left", named.name), left));
+                    views.push((format!("{}_right", named.name), right));
+                }
+                None => views.push((named.name.clone(), named.camera)),
+            }
+        }
+        views
+    }
 
-        let bunny_shapes = self.bunnies.iter().flat_map(|b| load_triangle(bunny_model, &b.transform, b.material.clone()).unwrap())
-                                        .map(|b| Arc::new(b) as Arc<dyn Shape>);
-        let cube_shapes = self.cubes.iter().flat_map(|c| draw_cube(&c.transform, &c.material));
+    /// Whether the geometry differs between frames.
+    pub fn has_animated_models(&self) -> bool {
+        self.cubes.iter()
+            .chain(self.bunnies.iter())
+            .any(|m| m.animation.is_some())
+            || self.meshes.iter().any(|m| m.animation.is_some())
+    }
+
+    /// All shapes of the scene. Mesh paths are resolved against `base_dir`.
+    /// Meshes already in `cache` are reused, so that animations load each mesh once.
+    pub fn split_to_shape(&self, base_dir: &Path, cache: &mut MeshCache) -> anyhow::Result<Vec<Arc<dyn Shape>>> {
+        // every bunny is an instance of the same mesh, parsed once
+        if let (None, Some(first)) = (&cache.bunny, self.bunnies.first()) {
+            let bunny_model = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/stanford-bunny.obj"));
+            cache.bunny = Some(Arc::new(load_obj(bunny_model, None, first.material.clone())?));
+        }
+        let bunny_mesh = cache.bunny.clone();
+        let bunny_shapes = self.bunnies.iter().map(|b| Arc::new(
+            Instance::new(bunny_mesh.clone().unwrap(), b.transform)
+                .with_transform_end(b.transform_end)
+                .with_material(Some(b.material.clone()))
+        ) as Arc<dyn Shape>);
+        let default_mesh_material: Arc<dyn Material> = Arc::new(Diffuse::new(0.8 * WHITE));
+        let mut mesh_shapes = vec![];
+        for mesh in &self.meshes {
+            // entries share a mesh if they also refine it the same way
+            let displacement = mesh.displacement.as_ref().map(|d| (d.texture.clone(), d.scale.to_bits()));
+            let key = (mesh.path.clone(), mesh.subdivision, displacement);
+            let shape = match cache.meshes.get(&key) {
+                Some(shape) => shape.clone(),
+                None => {
+                    let path = base_dir.join(&mesh.path);
+                    let loaded = load_mesh_file(&path, default_mesh_material.clone())?;
+                    let texture = match &mesh.displacement {
+                        Some(d) => Some((ImageTexture::load(base_dir.join(&d.texture))?, d.scale)),
+                        None => None,
+                    };
+                    let refined = refine_mesh(loaded, mesh.subdivision, texture.as_ref().map(|(t, scale)| (t, *scale)))
+                        .with_context(|| format!("failed to refine mesh {}", path.display()))?;
+                    let shape: Arc<dyn Shape> = Arc::new(refined);
+                    cache.meshes.insert(key, shape.clone());
+                    shape
+                }
+            };
+            mesh_shapes.push(Arc::new(
+                Instance::new(shape, mesh.transform)
+                    .with_transform_end(mesh.transform_end)
+                    .with_material(mesh.material.clone())
+            ) as Arc<dyn Shape>);
+        }
+
+        let cube_shapes = self.cubes.iter().flat_map(|c| draw_cube(&c.transform, c.transform_end.as_ref(), &c.material));
         let sphere_shapes = self.spheres.iter().map(|s| Arc::new(s.clone()) as Arc<dyn Shape>);
 
-        bunny_shapes.chain(cube_shapes).chain(sphere_shapes).collect()
+        let instance_shapes = self.instances.iter().map(|i| {
+            let object = self.objects.get(&i.object)
+                .ok_or_else(|| anyhow::anyhow!("unknown object {} in instances", i.object))?;
+            Ok(Arc::new(
+                Instance::new(object.clone(), i.transform)
+                    .with_transform_end(i.transform_end)
+                    .with_material(i.material.clone())
+            ) as Arc<dyn Shape>)
+        }).collect::<anyhow::Result<Vec<_>>>()?;
+
+        let curve_shapes = self.curves.iter().map(CurveInfo::segments).collect::<anyhow::Result<Vec<_>>>()?.into_iter().flatten();
+
+        let other_shapes = self.shapes.iter().cloned();
+
+        Ok(bunny_shapes.chain(mesh_shapes).chain(cube_shapes).chain(sphere_shapes).chain(instance_shapes)
+            .chain(curve_shapes).chain(other_shapes).collect())
     }
-}
\ No newline at end of file
+}
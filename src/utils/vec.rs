@@ -1,4 +1,4 @@
-use nalgebra_glm::DVec3;
+use nalgebra_glm::{DVec2, DVec3};
 use rand::prelude::*;
 pub fn random_in_unit_sphere() -> DVec3 {
     let mut rng = rand::thread_rng();
@@ -9,4 +9,21 @@ pub fn random_in_unit_sphere() -> DVec3 {
     let k = u.sample(&mut rng).cbrt();
     
     k * vec
-}
\ No newline at end of file
+}
+pub fn random_in_unit_disk() -> DVec2 {
+    let mut rng = rand::thread_rng();
+    let u = rand_distr::Uniform::new(0.0, 1.0f64);
+
+    let r = u.sample(&mut rng).sqrt();
+    let theta = 2.0 * std::f64::consts::PI * u.sample(&mut rng);
+
+    DVec2::new(r * theta.cos(), r * theta.sin())
+}
+
+/// Two unit vectors that form a right-handed orthonormal basis together with the unit vector `n`.
+pub fn orthonormal_basis(n: &DVec3) -> (DVec3, DVec3) {
+    let helper = if n.x.abs() > 0.9 { DVec3::new(0.0, 1.0, 0.0) } else { DVec3::new(1.0, 0.0, 0.0) };
+    let t = helper.cross(n).normalize();
+    let b = n.cross(&t);
+    (t, b)
+}